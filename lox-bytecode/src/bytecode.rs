@@ -57,6 +57,9 @@ pub struct Module {
     pub identifiers: Vec<String>,
     pub numbers: Vec<f64>,
     pub strings: Vec<String>,
+    /// The globals other modules can import, or `None` if the module has no `export` declarations
+    /// and all of its globals can be imported.
+    pub exports: Option<Vec<IdentifierIndex>>,
 }
 
 impl std::fmt::Debug for Module {
@@ -74,6 +77,7 @@ impl Module {
             identifiers: vec![],
             numbers: Vec::new(),
             strings: Vec::new(),
+            exports: None,
         }
     }
 
@@ -108,6 +112,10 @@ impl Module {
         self.identifiers.len() - 1
     }
 
+    pub fn add_export(&mut self, identifier: IdentifierIndex) {
+        self.exports.get_or_insert_with(Vec::new).push(identifier);
+    }
+
     pub fn add_number(&mut self, value: f64) -> ConstantIndex {
         self.numbers.push(value);
         self.numbers.len() - 1
@@ -130,6 +138,10 @@ impl Module {
         &self.identifiers
     }
 
+    pub fn exports(&self) -> Option<&[IdentifierIndex]> {
+        self.exports.as_deref()
+    }
+
     #[inline]
     pub fn number(&self, index: ConstantIndex) -> f64 {
         unsafe {
@@ -52,6 +52,10 @@ pub const STRING       : u8 = 39;
 
 pub const RETURN_TOP   : u8 = 40;
 
+pub const FIELDS       : u8 = 41;
+pub const STATIC_METHOD: u8 = 42;
+pub const STATIC_FIELD : u8 = 43;
+
 #[derive(Copy, Clone, Debug)]
 pub enum Opcode {
     True,
@@ -105,6 +109,10 @@ pub enum Opcode {
     String(u16),
 
     ReturnTop,
+
+    Fields,
+    StaticMethod(u32),
+    StaticField(u32),
 }
 
 pub struct OpcodeIterator<T: Iterator<Item = u8>> {
@@ -202,6 +210,10 @@ impl<T> Iterator for OpcodeIterator<T> where T: Iterator<Item = u8> {
 
             RETURN_TOP => Opcode::ReturnTop,
 
+            FIELDS => Opcode::Fields,
+            STATIC_METHOD => Opcode::StaticMethod(self.next_u32()),
+            STATIC_FIELD => Opcode::StaticField(self.next_u32()),
+
             _ => unreachable!(),
         };
 
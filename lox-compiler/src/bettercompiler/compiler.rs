@@ -298,6 +298,11 @@ impl Compiler {
         }
     }
 
+    pub fn add_export(&mut self, identifier: &str) {
+        let index = self.add_identifier(identifier);
+        self.module.add_export(index);
+    }
+
     pub fn resolve_upvalue(&mut self, name: &str) -> Option<StackIndex> {
         for i in (0..(self.contexts.len() - 1)).rev() {
             // Skip the current context
@@ -2,7 +2,7 @@ use super::compiler::Compiler;
 use super::compiler::ContextType;
 use crate::bytecode::*;
 use lox_syntax::ast::*;
-use lox_syntax::position::WithSpan;
+use lox_syntax::position::{Span, WithSpan};
 use lox_bytecode::opcode;
 
 pub fn compile_ast(compiler: &mut Compiler, ast: &Ast) {
@@ -46,6 +46,9 @@ fn compile_stmt(compiler: &mut Compiler, stmt: &WithSpan<Stmt>) {
             compile_class(compiler, identifier.as_ref(), extends.as_ref(), stmts)
         }
         Stmt::Import(path, identifiers) => compile_import(compiler, path, identifiers.as_ref()),
+        Stmt::ImportAs(path, alias) => compile_import_as(compiler, path, alias.as_ref()),
+        Stmt::Export(declaration) => compile_export(compiler, stmt.span, declaration),
+        Stmt::Static(_) => compiler.add_error("Static members can only be declared in a class body", stmt.span),
     }
 }
 
@@ -90,6 +93,34 @@ fn compile_import(compiler: &mut Compiler, path: &WithSpan<String>, identifiers:
     compiler.add_u8(opcode::POP);
 }
 
+fn compile_import_as(compiler: &mut Compiler, path: &WithSpan<String>, alias: WithSpan<&String>) {
+    declare_variable(compiler, alias.as_ref());
+
+    let constant = compiler.add_string(path.value.as_str());
+    compiler.add_u8(opcode::IMPORT);
+    compiler.add_u32(constant as _);
+
+    define_variable(compiler, alias.value);
+}
+
+fn compile_export(compiler: &mut Compiler, span: Span, declaration: &WithSpan<Stmt>) {
+    if compiler.context_type() != ContextType::TopLevel || compiler.is_scoped() {
+        compiler.add_error("Can only export top-level declarations", span);
+        return;
+    }
+
+    let identifier = match &declaration.value {
+        Stmt::Var(identifier, _) | Stmt::Function(identifier, _, _) | Stmt::Class(identifier, _, _) => identifier,
+        _ => {
+            compiler.add_error("Can only export declarations", span);
+            return;
+        },
+    };
+
+    compiler.add_export(&identifier.value);
+    compile_stmt(compiler, declaration);
+}
+
 fn compile_class(
     compiler: &mut Compiler,
     identifier: WithSpan<&String>,
@@ -104,23 +135,106 @@ fn compile_class(
     compiler.add_u8(constant as _);
     define_variable(compiler, identifier.value);
 
+    let name = identifier.value;
     compile_variable(compiler, identifier);
 
     //TODO Extends
 
-    // Methods
+    let mut fields: Vec<(&WithSpan<Identifier>, Option<&WithSpan<Expr>>)> = Vec::new();
     for stmt in stmts {
         match &stmt.value {
             Stmt::Function(identifier, args, block) => {
                 compile_method(compiler, identifier.as_ref(), args, block);
             },
-            _ => unimplemented!(), //TODO
+            Stmt::Var(identifier, initializer) => {
+                if fields.iter().any(|(field, _)| field.value == identifier.value) {
+                    compiler.add_error("Field already declared", identifier.span);
+                }
+                fields.push((identifier, initializer.as_deref()));
+            },
+            Stmt::Static(member) => compile_static_member(compiler, member),
+            _ => compiler.add_error("Expected a method or field declaration", stmt.span),
         }
     }
 
+    if !fields.is_empty() {
+        compile_fields(compiler, name, &fields);
+    }
+
     compiler.add_u8(opcode::POP);
 }
 
+/// Compile the field declarations into a closure that sets them on a new instance before `init` runs.
+fn compile_fields(
+    compiler: &mut Compiler,
+    class_name: &str,
+    fields: &[(&WithSpan<Identifier>, Option<&WithSpan<Expr>>)],
+) {
+    let (chunk_index, upvalues) =
+    compiler.with_scoped_context(ContextType::Method, |compiler| {
+        for (field, initializer) in fields {
+            compiler.add_u8(opcode::GET_LOCAL);
+            compiler.add_u32(0);
+
+            match initializer {
+                Some(initializer) => compile_expr(compiler, initializer),
+                None => compile_nil(compiler),
+            }
+
+            let constant = compiler.add_identifier(field.value.as_str());
+            compiler.add_u8(opcode::SET_PROPERTY);
+            compiler.add_u32(constant as _);
+            compiler.add_u8(opcode::POP);
+        }
+
+        compile_return_top(compiler);
+    });
+
+    let closure = Closure {
+        function: Function {
+            name: class_name.into(),
+            chunk_index,
+            arity: 0,
+        },
+        upvalues,
+    };
+
+    let constant = compiler.add_closure(closure);
+    compiler.add_u8(opcode::CLOSURE);
+    compiler.add_u32(constant as _);
+    compiler.add_u8(opcode::FIELDS);
+}
+
+/// A `class` method, called on the class itself as `this`, or a `class var` field, initialized when the class is declared.
+fn compile_static_member(compiler: &mut Compiler, member: &WithSpan<Stmt>) {
+    let (identifier, opcode) = match &member.value {
+        Stmt::Function(identifier, args, block) => {
+            if identifier.value == "init" {
+                compiler.add_error("Static methods can't be named 'init'", identifier.span);
+                return;
+            }
+
+            compile_closure(compiler, &identifier.as_ref(), args, block, ContextType::Method);
+            (identifier, opcode::STATIC_METHOD)
+        },
+        Stmt::Var(identifier, initializer) => {
+            match initializer {
+                Some(initializer) => compile_expr(compiler, initializer),
+                None => compile_nil(compiler),
+            }
+            (identifier, opcode::STATIC_FIELD)
+        },
+        _ => {
+            compiler.add_error("Expected a method or field declaration", member.span);
+            return;
+        },
+    };
+
+    let constant = compiler.add_identifier(identifier.value.as_str());
+    compiler.add_u8(opcode);
+    compiler.add_u32(constant as _);
+}
+
 fn compile_method(
     compiler: &mut Compiler,
     identifier: WithSpan<&String>,
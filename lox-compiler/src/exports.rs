@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use lox_bytecode::bytecode::Module;
+use lox_syntax::ast::{Ast, Stmt};
+use lox_syntax::position::{Diagnostic, WithSpan};
+
+/// The globals a module lets other modules import.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exports {
+    /// The module has no `export` declarations, so all of its globals can be imported.
+    All,
+    Names(Vec<String>),
+}
+
+impl Exports {
+    pub fn of(module: &Module) -> Self {
+        match module.exports() {
+            Some(exports) => Exports::Names(exports.iter().map(|index| module.identifier(*index).to_string()).collect()),
+            None => Exports::All,
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        match self {
+            Exports::All => true,
+            Exports::Names(names) => names.iter().any(|export| export == name),
+        }
+    }
+}
+
+pub(crate) fn exports(ast: &Ast) -> Exports {
+    let mut names = None;
+    for stmt in ast {
+        if let Stmt::Export(declaration) = &stmt.value {
+            let names = names.get_or_insert_with(Vec::new);
+            if let Some(identifier) = declared_name(declaration) {
+                names.push(identifier.value.clone());
+            }
+        }
+    }
+
+    names.map_or(Exports::All, Exports::Names)
+}
+
+fn declared_name(stmt: &WithSpan<Stmt>) -> Option<&WithSpan<String>> {
+    match &stmt.value {
+        Stmt::Var(identifier, _) | Stmt::Function(identifier, _, _) | Stmt::Class(identifier, _, _) => Some(identifier),
+        _ => None,
+    }
+}
+
+/// Check that every `import "path" for name;` in `ast` names a global that `path` exports.
+/// Paths for which `exports` returns `None` are not checked.
+pub(crate) fn check_imports(ast: &Ast, exports: &mut dyn FnMut(&str) -> Option<Exports>) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        exports,
+        modules: HashMap::new(),
+        diagnostics: Vec::new(),
+    };
+
+    checker.check(ast);
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    exports: &'a mut dyn FnMut(&str) -> Option<Exports>,
+    modules: HashMap<String, Option<Exports>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn check(&mut self, stmts: &[WithSpan<Stmt>]) {
+        for stmt in stmts {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &WithSpan<Stmt>) {
+        match &stmt.value {
+            Stmt::Import(path, Some(identifiers)) => self.check_import(path, identifiers),
+            Stmt::Block(stmts) | Stmt::Function(_, _, stmts) | Stmt::Class(_, _, stmts) => self.check(stmts),
+            Stmt::If(_, then_stmt, else_stmt) => {
+                self.check_stmt(then_stmt);
+                if let Some(else_stmt) = else_stmt {
+                    self.check_stmt(else_stmt);
+                }
+            },
+            Stmt::While(_, body) => self.check_stmt(body),
+            Stmt::Export(declaration) | Stmt::Static(declaration) => self.check_stmt(declaration),
+            _ => {},
+        }
+    }
+
+    fn check_import(&mut self, path: &WithSpan<String>, identifiers: &[WithSpan<String>]) {
+        let exports = &mut self.exports;
+        let exports = self.modules
+            .entry(path.value.clone())
+            .or_insert_with(|| exports(&path.value));
+
+        let Some(exports) = exports else {
+            return;
+        };
+
+        for identifier in identifiers {
+            if !exports.contains(&identifier.value) {
+                self.diagnostics.push(Diagnostic {
+                    message: format!("'{}' is not exported by \"{}\"", identifier.value, path.value),
+                    span: identifier.span,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(code: &str, exports: &str) -> Vec<String> {
+        let ast = lox_syntax::parse(code).unwrap();
+        let exports = super::exports(&lox_syntax::parse(exports).unwrap());
+        check_imports(&ast, &mut |_| Some(exports.clone()))
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn exports() {
+        let ast = lox_syntax::parse("var a; export var b; export fun c() {} export class D {}").unwrap();
+        assert_eq!(super::exports(&ast), Exports::Names(vec!["b".into(), "c".into(), "D".into()]));
+
+        let ast = lox_syntax::parse("var a; fun b() {}").unwrap();
+        assert_eq!(super::exports(&ast), Exports::All);
+    }
+
+    #[test]
+    fn imports() {
+        assert!(check("import \"m\" for a;", "var a;").is_empty());
+        assert!(check("import \"m\" for a;", "export var a; var b;").is_empty());
+        assert_eq!(check("import \"m\" for a, b;", "export var a; var b;"), ["'b' is not exported by \"m\""]);
+        assert_eq!(check("fun f() { { import \"m\" for b; } }", "export var a;"), ["'b' is not exported by \"m\""]);
+        assert!(check("import \"m\" as m;", "export var a;").is_empty());
+    }
+}
@@ -1,8 +1,10 @@
 mod bettercompiler;
+mod exports;
 
 use lox_bytecode::bytecode;
 pub use lox_syntax::position::Diagnostic;
 pub use lox_syntax::position::LineOffsets;
+pub use exports::Exports;
 
 //TODO Better errors
 
@@ -14,3 +16,25 @@ pub fn compile(code: &str) -> Result<Module, Vec<Diagnostic>> {
 
     Ok(module)
 }
+
+/// Parse `code` only as far as needed to find what it exports.
+pub fn exports(code: &str) -> Result<Exports, Vec<Diagnostic>> {
+    let ast = lox_syntax::parse(code)?;
+    Ok(exports::exports(&ast))
+}
+
+/// Like [`compile`], but every `import "path" for name;` must name a global that `path` exports.
+/// `exports` gives the exports of the module at `path`, or `None` to not check imports from it.
+pub fn compile_with_exports(code: &str, mut exports: impl FnMut(&str) -> Option<Exports>) -> Result<Module, Vec<Diagnostic>> {
+    let ast = lox_syntax::parse(code)?;
+    let mut diagnostics = exports::check_imports(&ast, &mut exports);
+
+    match bettercompiler::compile(&ast) {
+        Ok(module) if diagnostics.is_empty() => Ok(module),
+        Ok(_) => Err(diagnostics),
+        Err(mut errors) => {
+            errors.append(&mut diagnostics);
+            Err(errors)
+        },
+    }
+}
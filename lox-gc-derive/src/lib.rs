@@ -0,0 +1,120 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields};
+
+/// Derives `lox_gc::Trace` by tracing every field.
+///
+/// Fields that cannot reference managed objects and do not implement `Trace`,
+/// such as function pointers, can be marked `#[trace(skip)]`.
+/// Skipping a field that does reference managed objects lets them be freed while still in use.
+#[proc_macro_derive(Trace, attributes(trace))]
+pub fn derive_trace(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::lox_gc::Trace));
+    }
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, calls) = trace_fields(&data.fields)?;
+            quote! {
+                let Self #pattern = self;
+                #(#calls)*
+            }
+        },
+        Data::Enum(data) if data.variants.is_empty() => quote! {
+            match *self {}
+        },
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let (pattern, calls) = trace_fields(&variant.fields)?;
+                arms.push(quote! {
+                    Self::#ident #pattern => {
+                        #(#calls)*
+                    }
+                });
+            }
+
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        },
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(&input.ident, "Trace cannot be derived for unions"));
+        },
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        unsafe impl #impl_generics ::lox_gc::Trace for #name #ty_generics #where_clause {
+            #[inline]
+            #[allow(unused_variables)]
+            fn trace(&self, tracer: &mut ::lox_gc::Tracer) {
+                #body
+            }
+        }
+    })
+}
+
+/// A pattern that binds every traced field, and the calls that trace them.
+fn trace_fields(fields: &Fields) -> syn::Result<(TokenStream2, Vec<TokenStream2>)> {
+    let mut bindings = Vec::new();
+    let mut calls = Vec::new();
+
+    for (index, field) in fields.iter().enumerate() {
+        let binding = if is_skipped(&field.attrs)? {
+            quote!(_)
+        } else {
+            let binding = format_ident!("__field{}", index);
+            calls.push(quote! {
+                ::lox_gc::Trace::trace(#binding, tracer);
+            });
+            quote!(#binding)
+        };
+
+        bindings.push(match &field.ident {
+            Some(ident) => quote!(#ident: #binding),
+            None => binding,
+        });
+    }
+
+    let pattern = match fields {
+        Fields::Named(_) => quote!({ #(#bindings),* }),
+        Fields::Unnamed(_) => quote!(( #(#bindings),* )),
+        Fields::Unit => quote!(),
+    };
+
+    Ok((pattern, calls))
+}
+
+fn is_skipped(attrs: &[Attribute]) -> syn::Result<bool> {
+    let mut skip = false;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("trace")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip`"))
+            }
+        })?;
+    }
+
+    Ok(skip)
+}
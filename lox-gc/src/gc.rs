@@ -1,5 +1,6 @@
-use std::{ptr::NonNull, ops::Deref, any::TypeId, cell::{Cell, RefCell}};
-use crate::heap;
+use std::{io, ptr::NonNull, ops::Deref, any::TypeId, cell::{Cell, RefCell}, marker::PhantomData, time::{Duration, Instant}};
+use crate::heap::{self, ClassStats, HeapConfig};
+use crate::snapshot::{Census, Snapshot};
 
 #[repr(C)]
 struct Allocation<T: ?Sized> {
@@ -22,22 +23,84 @@ impl<T: Trace + 'static> Allocation<T> {
 
 pub struct Tracer<'heap> {
     heap: &'heap ManagedHeap,
+    /// Set while taking a snapshot, which records objects instead of marking them.
+    census: Option<&'heap Census>,
 }
 
 impl Tracer<'_> {
+    /// Mark a buffer from [`crate::alloc`] that the object being traced owns.
     pub unsafe fn mark(&self, ptr: *const u8) {
-        self.heap.heap.mark(ptr);
+        match self.census {
+            Some(census) => census.add_buffer(ptr, self.heap.heap.block_bytes(ptr)),
+            None => self.heap.heap.mark(ptr),
+        }
     }
 }
 
 pub unsafe trait Trace {
     fn trace(&self, tracer: &mut Tracer);
+
+    /// The name heap snapshots report for this type.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// How the heap collects garbage once it grows past its threshold.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Collector {
+    /// Mark and sweep the whole heap in one pause.
+    #[default]
+    StopTheWorld,
+
+    /// Mark a bounded number of objects per collection point and only pause
+    /// for a final root scan and the sweep.
+    ///
+    /// While marking is in progress every store of a reference into a heap
+    /// object must go through [`crate::write_barrier`].
+    Incremental,
+}
+
+/// A snapshot of what the collector has done so far.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    /// Completed collection cycles.
+    pub collections: usize,
+    pub bytes_allocated: usize,
+    pub bytes_freed: usize,
+    /// Bytes of empty pages given back to the OS.
+    pub bytes_released: usize,
+    pub live_bytes: usize,
+    /// Live bytes for every size class, reams first.
+    pub classes: Vec<ClassStats>,
+    /// The heap size that starts the next collection.
+    pub threshold: usize,
+    /// Number of times the mutator was paused. An incremental cycle pauses once per step.
+    pub pauses: usize,
+    pub total_pause: Duration,
+    pub max_pause: Duration,
 }
 
 pub struct ManagedHeap {
     pub(crate) heap: heap::Heap,
     finalizers: RefCell<Vec<Gc<()>>>,
     threshold: Cell<usize>,
+    growth_factor: Cell<f32>,
+    collections: Cell<usize>,
+    pauses: Cell<usize>,
+    total_pause: Cell<Duration>,
+    max_pause: Cell<Duration>,
+    collector: Cell<Collector>,
+    /// Marked objects whose children have not been traced yet.
+    gray: RefCell<Vec<Gc<()>>>,
+    /// Every live weak reference, cleared once its target is found unreachable.
+    weaks: RefCell<Vec<Gc<WeakCell>>>,
+    /// Whether an incremental mark is in progress.
+    marking: Cell<bool>,
+    /// Roots traced by every collection, in addition to the ones passed in.
+    roots: RefCell<Vec<*const dyn Trace>>,
+    /// Collect on every allocation.
+    stress: Cell<bool>,
 }
 
 impl Drop for ManagedHeap {
@@ -51,13 +114,111 @@ impl Drop for ManagedHeap {
 }
 
 impl ManagedHeap {
-    const THRESHOLD_ADJ: f32 = 2.0;
+    const INITIAL_THRESHOLD: usize = 1024 * 1024;
+    const GROWTH_FACTOR: f32 = 2.0;
 
-    pub fn new() -> Self {
-        Self {
-            threshold: Cell::new(1024 * 1024),
-            heap: heap::Heap::new().unwrap(),
+    /// The number of objects traced per incremental step.
+    const INCREMENTAL_STEP: usize = 256;
+
+    pub fn new(config: HeapConfig) -> io::Result<Self> {
+        Ok(Self {
+            threshold: Cell::new(Self::INITIAL_THRESHOLD),
+            growth_factor: Cell::new(Self::GROWTH_FACTOR),
+            collections: Cell::new(0),
+            pauses: Cell::new(0),
+            total_pause: Cell::new(Duration::ZERO),
+            max_pause: Cell::new(Duration::ZERO),
+            heap: heap::Heap::new(config)?,
             finalizers: RefCell::new(Vec::new()),
+            collector: Cell::new(Collector::StopTheWorld),
+            gray: RefCell::new(Vec::new()),
+            weaks: RefCell::new(Vec::new()),
+            marking: Cell::new(false),
+            roots: RefCell::new(Vec::new()),
+            stress: Cell::new(cfg!(feature = "stress")),
+        })
+    }
+
+    pub fn collector(&self) -> Collector {
+        self.collector.get()
+    }
+
+    /// Switching collectors takes effect at the next collection.
+    /// A mark that is already in progress is finished by that collection.
+    pub fn set_collector(&self, collector: Collector) {
+        self.collector.set(collector);
+    }
+
+    /// Collect once more than `bytes` are in use.
+    /// After every collection the threshold is reset to the live bytes times the growth factor.
+    pub fn set_threshold(&self, bytes: usize) {
+        self.threshold.set(bytes);
+    }
+
+    pub fn set_growth_factor(&self, factor: f32) {
+        self.growth_factor.set(factor);
+    }
+
+    pub fn collections(&self) -> usize {
+        self.collections.get()
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            collections: self.collections.get(),
+            bytes_allocated: self.heap.bytes_allocated(),
+            bytes_freed: self.heap.bytes_freed(),
+            bytes_released: self.heap.bytes_released(),
+            live_bytes: self.heap.bytes_used(),
+            classes: self.heap.class_stats(),
+            threshold: self.threshold.get(),
+            pauses: self.pauses.get(),
+            total_pause: self.total_pause.get(),
+            max_pause: self.max_pause.get(),
+        }
+    }
+
+    /// Force a full collection on every `manage` and `alloc`, and poison swept memory.
+    /// Returns the previous setting.
+    ///
+    /// Only registered roots and the value being managed are traced by these collections,
+    /// so any `Gc` held elsewhere across an allocation is freed.
+    pub fn set_stress(&self, enabled: bool) -> bool {
+        self.stress.replace(enabled)
+    }
+
+    pub fn is_stress(&self) -> bool {
+        self.stress.get()
+    }
+
+    /// Trace `root` on every collection until it is removed again.
+    ///
+    /// # Safety
+    /// `root` must stay valid until it is passed to [`ManagedHeap::remove_root`].
+    pub unsafe fn add_root(&self, root: *const dyn Trace) {
+        self.roots.borrow_mut().push(root);
+    }
+
+    pub fn remove_root(&self, root: *const dyn Trace) {
+        let mut roots = self.roots.borrow_mut();
+        if let Some(index) = roots.iter().rposition(|other| std::ptr::addr_eq(*other, root)) {
+            roots.swap_remove(index);
+        }
+    }
+
+    pub fn is_marking(&self) -> bool {
+        self.marking.get()
+    }
+
+    /// Shade everything `value` references while an incremental mark is in progress.
+    /// This is a no-op otherwise.
+    #[inline]
+    pub fn write_barrier<T: Trace + ?Sized>(&self, value: &T) {
+        if self.marking.get() {
+            value.trace(&mut Tracer {
+                heap: self,
+                census: None,
+            });
         }
     }
 
@@ -84,10 +245,18 @@ impl ManagedHeap {
     }
 
     pub unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        if self.stress.get() {
+            self.force_collect(&[]);
+        }
+
         self.heap.alloc(layout)
     }
 
     pub fn manage<T>(&self, data: T) -> Gc<T> where T: Trace + 'static {
+        if self.stress.get() {
+            self.force_collect(&[&data]);
+        }
+
         let layout = std::alloc::Layout::new::<Allocation<T>>();
         let ptr = self.heap.alloc(layout) as *mut Allocation<T>;
         let gc = unsafe {
@@ -103,34 +272,180 @@ impl ManagedHeap {
             self.finalize(gc.erase());
         }
 
+        // New objects are allocated marked, so whatever they were created with
+        // has to be shaded as well.
+        self.write_barrier(&*gc);
+
         gc
     }
 
+    pub fn downgrade<T: ?Sized>(&self, gc: Gc<T>) -> Weak<T> {
+        let cell = self.manage(WeakCell {
+            target: Cell::new(Some(gc.erase_unsized())),
+        });
+
+        self.weaks.borrow_mut().push(cell);
+
+        Weak {
+            cell,
+            marker: PhantomData,
+        }
+    }
+
     pub fn collect(&self, roots: &[&dyn Trace]) {
-        if self.heap.bytes_used() > self.threshold.get() {
-            self.force_collect(roots);
-            self.threshold.set(((self.heap.bytes_used() as f32 * Self::THRESHOLD_ADJ) as usize) + 100);
+        if self.marking.get() {
+            match self.collector.get() {
+                Collector::Incremental => self.pause(|| self.step(roots)),
+                Collector::StopTheWorld => self.pause(|| self.finish(roots)),
+            }
+        } else if self.heap.bytes_used() > self.threshold.get() {
+            match self.collector.get() {
+                Collector::Incremental => self.pause(|| self.start(roots)),
+                Collector::StopTheWorld => self.force_collect(roots),
+            }
         }
     }
 
     pub fn force_collect(&self, roots: &[&dyn Trace]) {
+        self.pause(|| {
+            if !self.marking.get() {
+                unsafe {
+                    self.heap.start_gc();
+                }
+            }
+
+            self.finish(roots);
+        });
+    }
+
+    /// Run `f`, recording how long the mutator was paused.
+    fn pause(&self, f: impl FnOnce()) {
+        let start = Instant::now();
+        f();
+        let duration = start.elapsed();
+
+        self.pauses.set(self.pauses.get() + 1);
+        self.total_pause.set(self.total_pause.get() + duration);
+        self.max_pause.set(self.max_pause.get().max(duration));
+    }
+
+    /// Start an incremental mark by shading the roots.
+    fn start(&self, roots: &[&dyn Trace]) {
         unsafe {
             self.heap.start_gc();
         }
 
+        self.marking.set(true);
+        self.trace_roots(roots);
+    }
+
+    /// Trace a bounded number of gray objects, finishing the cycle once none are left.
+    fn step(&self, roots: &[&dyn Trace]) {
+        if self.drain(Self::INCREMENTAL_STEP) {
+            self.finish(roots);
+        }
+    }
+
+    /// Rescan the roots, mark everything still reachable and sweep.
+    fn finish(&self, roots: &[&dyn Trace]) {
+        self.trace_roots(roots);
+        self.drain(usize::MAX);
+        self.marking.set(false);
+
+        self.clear_weaks();
+        self.force_finalize();
+
+        unsafe {
+            if self.stress.get() {
+                self.heap.poison_unmarked();
+            }
+
+            self.heap.sweep();
+        }
+
+        self.collections.set(self.collections.get() + 1);
+        self.threshold.set(((self.heap.bytes_used() as f32 * self.growth_factor.get()) as usize) + 100);
+    }
+
+    /// Drop weak references that are unreachable themselves and clear those whose target is.
+    fn clear_weaks(&self) {
+        self.weaks.borrow_mut().retain(|cell| {
+            if !self.heap.is_marked(cell.ptr.as_ptr() as *const u8) {
+                return false;
+            }
+
+            if let Some(target) = cell.target.get() {
+                if !self.heap.is_marked(target.ptr.as_ptr() as *const u8) {
+                    cell.target.set(None);
+                }
+            }
+
+            true
+        });
+    }
+
+    fn trace_roots(&self, roots: &[&dyn Trace]) {
         let mut tracer = Tracer {
             heap: self,
+            census: None,
         };
 
+        self.trace_roots_with(roots, &mut tracer);
+    }
+
+    fn trace_roots_with(&self, roots: &[&dyn Trace], tracer: &mut Tracer) {
         for root in roots {
-            root.trace(&mut tracer);
+            root.trace(tracer);
         }
-        
-        self.force_finalize();
 
-        unsafe {
-            self.heap.sweep();
+        // Tracing never registers roots, so the borrow is not contended.
+        for root in self.roots.borrow().iter() {
+            unsafe {
+                (**root).trace(tracer);
+            }
+        }
+    }
+
+    /// Record every object reachable from `roots` and the registered roots.
+    /// A root referencing `skip_root` directly is ignored.
+    /// This does not touch the marks, so it is safe during an incremental mark.
+    pub fn snapshot(&self, roots: &[&dyn Trace], skip_root: Option<Gc<()>>) -> Snapshot {
+        let census = Census::new(skip_root);
+        let mut tracer = Tracer {
+            heap: self,
+            census: Some(&census),
+        };
+
+        self.trace_roots_with(roots, &mut tracer);
+
+        let mut next = 0;
+        while let Some(object) = census.object(next) {
+            census.set_current(next);
+            object.dyn_data().trace(&mut tracer);
+            next += 1;
+        }
+
+        census.into_snapshot()
+    }
+
+    /// Trace at most `budget` gray objects. Returns whether the gray set is empty.
+    fn drain(&self, mut budget: usize) -> bool {
+        let mut tracer = Tracer {
+            heap: self,
+            census: None,
+        };
+
+        while budget > 0 {
+            let next = self.gray.borrow_mut().pop();
+            match next {
+                Some(gc) => gc.dyn_data().trace(&mut tracer),
+                None => return true,
+            }
+
+            budget -= 1;
         }
+
+        self.gray.borrow().is_empty()
     }
 }
 
@@ -153,6 +468,10 @@ impl<T: ?Sized> Gc<T> {
 }
 
 impl Gc<()> {
+    pub fn type_id(self) -> TypeId {
+        self.allocation().tag
+    }
+
     pub fn is<T>(self) -> bool where T: 'static {
         self.allocation().tag == TypeId::of::<T>()
     }
@@ -215,6 +534,54 @@ impl<T: ?Sized> Deref for Gc<T> {
     }
 }
 
+/// A reference that does not keep its target alive.
+pub struct Weak<T: ?Sized> {
+    cell: Gc<WeakCell>,
+    marker: PhantomData<*const T>,
+}
+
+struct WeakCell {
+    target: Cell<Option<Gc<()>>>,
+}
+
+unsafe impl Trace for WeakCell {
+    fn trace(&self, _tracer: &mut Tracer) {}
+}
+
+impl<T: ?Sized> Copy for Weak<T> {}
+impl<T: ?Sized> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Weak<T> {
+    /// Returns the target, or `None` once it has been collected.
+    pub fn upgrade(&self) -> Option<Gc<T>> where T: Sized {
+        let target = self.cell.target.get()?;
+
+        // The target may be unmarked while an incremental mark is in progress.
+        crate::write_barrier(&target);
+
+        Some(Gc {
+            ptr: target.ptr.cast(),
+        })
+    }
+
+    pub fn erase(self) -> Weak<()> {
+        Weak {
+            cell: self.cell,
+            marker: PhantomData,
+        }
+    }
+}
+
+unsafe impl<T: ?Sized> Trace for Weak<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.cell.trace(tracer);
+    }
+}
+
 impl<T: ?Sized> PartialEq for Gc<T> {
     fn eq(&self, other: &Self) -> bool {
         Gc::ptr_eq(*self, *other)
@@ -234,6 +601,12 @@ impl<T: ?Sized> Gc<T> {
         }
     }
 
+    fn erase_unsized(&self) -> Gc<()> {
+        Gc {
+            ptr: self.ptr.cast(),
+        }
+    }
+
     fn dyn_data(&self) -> &dyn Trace {
         let ptr = self.ptr.as_ptr() as *const Allocation<()>;
         unsafe {
@@ -255,12 +628,18 @@ unsafe impl<T: ?Sized> Trace for Gc<T> {
     fn trace(&self, tracer: &mut Tracer) {
         let ptr = self.ptr.as_ptr() as *const u8;
 
+        if let Some(census) = tracer.census {
+            let bytes = tracer.heap.heap.block_bytes(ptr);
+            census.visit(self.erase_unsized(), self.dyn_data().type_name(), bytes);
+            return;
+        }
+
         if !tracer.heap.heap.is_marked(ptr) {
             unsafe {
                 tracer.heap.heap.mark(ptr);
             }
 
-            self.dyn_data().trace(tracer);
+            tracer.heap.gray.borrow_mut().push(self.erase_unsized());
         }
     }
 }
@@ -306,11 +685,45 @@ mod trace_impls {
     use super::*;
     use std::collections::HashMap;
     use std::hash::Hash;
-    use std::cell::{UnsafeCell, Cell};
+    use std::cell::{UnsafeCell, Cell, RefCell};
     use arrayvec::ArrayVec;
 
-    unsafe impl Trace for String {
-        fn trace(&self, _tracer: &mut Tracer) {}
+    /// Types that never reference managed objects.
+    macro_rules! leaf {
+        ($($type:ty),*) => {
+            $(
+                unsafe impl Trace for $type {
+                    #[inline]
+                    fn trace(&self, _tracer: &mut Tracer) {}
+                }
+            )*
+        };
+    }
+
+    leaf!(
+        (), bool, char, f32, f64,
+        u8, u16, u32, u64, u128, usize,
+        i8, i16, i32, i64, i128, isize,
+        String, TypeId
+    );
+
+    unsafe impl<T: Trace + ?Sized> Trace for &T {
+        fn trace(&self, tracer: &mut Tracer) {
+            (**self).trace(tracer);
+        }
+    }
+
+    unsafe impl<A: Trace, B: Trace> Trace for (A, B) {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+            self.1.trace(tracer);
+        }
+    }
+
+    unsafe impl<T: Trace> Trace for RefCell<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.borrow().trace(tracer);
+        }
     }
 
     unsafe impl<T: Trace> Trace for Option<T> {
@@ -369,18 +782,244 @@ mod trace_impls {
 #[cfg(test)]
 mod test {
     use super::*;
+    use lox_gc_derive::Trace;
 
-    unsafe impl Trace for u32 {
-        fn trace(&self, _tracer: &mut Tracer) {}
+    /// A heap that only collects when asked to, even with the `stress` feature.
+    fn heap() -> ManagedHeap {
+        let heap = ManagedHeap::new(HeapConfig::default()).unwrap();
+        heap.set_stress(false);
+        heap
     }
 
-
     #[test]
     fn it_works() {
-        let heap = ManagedHeap::new();
+        let heap = heap();
         let x = heap.manage(std::cell::Cell::new(1234));
         assert_eq!(x.get(), 1234);
         x.set(2345);
         assert_eq!(x.get(), 2345);
     }
+
+    #[derive(Trace)]
+    struct Node {
+        next: Cell<Option<Gc<Node>>>,
+    }
+
+    fn node(heap: &ManagedHeap, next: Option<Gc<Node>>) -> Gc<Node> {
+        heap.manage(Node {
+            next: Cell::new(next),
+        })
+    }
+
+    fn is_marked<T>(heap: &ManagedHeap, gc: Gc<T>) -> bool {
+        heap.heap.is_marked(gc.ptr.as_ptr() as *const u8)
+    }
+
+    #[derive(Trace)]
+    struct Pair<T> {
+        first: T,
+        second: Option<Gc<Node>>,
+        #[trace(skip)]
+        _callback: fn(),
+    }
+
+    #[derive(Trace)]
+    enum Shape {
+        Empty,
+        One(Gc<Node>),
+        Two {
+            first: Gc<Node>,
+            #[trace(skip)]
+            _callback: fn(),
+            second: Gc<Node>,
+        },
+    }
+
+    #[test]
+    fn derive() {
+        let heap = heap();
+
+        let nodes: Vec<_> = (0..5).map(|_| node(&heap, None)).collect();
+        let garbage = node(&heap, None);
+
+        let pair = Pair {
+            first: nodes[0],
+            second: Some(nodes[1]),
+            _callback: || (),
+        };
+        let shapes = vec![
+            Shape::Empty,
+            Shape::One(nodes[2]),
+            Shape::Two {
+                first: nodes[3],
+                _callback: || (),
+                second: nodes[4],
+            },
+        ];
+
+        heap.force_collect(&[&pair, &shapes]);
+        assert!(nodes.iter().all(|node| is_marked(&heap, *node)));
+        assert!(!is_marked(&heap, garbage));
+    }
+
+    #[test]
+    fn incremental_write_barrier() {
+        let heap = heap();
+        heap.set_collector(Collector::Incremental);
+
+        let c = node(&heap, None);
+        let d = node(&heap, Some(c));
+        let a = node(&heap, None);
+
+        // Blacken `a` while `d` is still gray and `c` is white.
+        heap.start(&[&d, &a]);
+        assert!(!heap.drain(1));
+        assert!(is_marked(&heap, a));
+        assert!(!is_marked(&heap, c));
+
+        // Move `c` from the gray object to the black one.
+        heap.write_barrier(&Some(c));
+        a.next.set(Some(c));
+        d.next.set(None);
+
+        // Objects allocated while marking survive.
+        let b = node(&heap, None);
+        a.next.set(Some(b));
+        b.next.set(Some(c));
+
+        heap.collect(&[&d, &a]);
+        assert!(!heap.is_marking());
+        assert!(is_marked(&heap, b));
+        assert!(is_marked(&heap, c));
+    }
+
+    #[test]
+    fn stats() {
+        let heap = heap();
+        heap.set_threshold(0);
+        heap.set_growth_factor(1.0);
+
+        let a = node(&heap, None);
+        node(&heap, None);
+
+        let before = heap.stats();
+        assert_eq!(before.collections, 0);
+        assert!(before.bytes_allocated >= 2 * std::mem::size_of::<Allocation<Node>>());
+
+        heap.collect(&[&a]);
+
+        let after = heap.stats();
+        assert_eq!(after.collections, 1);
+        assert_eq!(after.pauses, 1);
+        assert_eq!(after.bytes_freed, before.live_bytes - after.live_bytes);
+        assert_eq!(after.live_bytes, after.classes.iter().map(|class| class.live_bytes).sum());
+        assert_eq!(after.threshold, after.live_bytes + 100);
+    }
+
+    #[test]
+    fn weak() {
+        let heap = heap();
+
+        let a = node(&heap, None);
+        let b = node(&heap, None);
+        let weak_a = heap.downgrade(a);
+        let weak_b = heap.downgrade(b);
+
+        heap.force_collect(&[&a, &weak_a, &weak_b]);
+        assert!(weak_a.upgrade() == Some(a));
+        assert!(weak_b.upgrade().is_none());
+
+        // Weak references that are unreachable themselves are forgotten.
+        heap.force_collect(&[&a]);
+        assert!(heap.weaks.borrow().is_empty());
+    }
+
+    #[test]
+    fn snapshot() {
+        let heap = heap();
+
+        let c = node(&heap, None);
+        let b = node(&heap, Some(c));
+        let a = node(&heap, Some(b));
+        let garbage = node(&heap, Some(c));
+        let weak = heap.downgrade(garbage);
+
+        let snapshot = heap.snapshot(&[&a, &weak], None);
+        assert_eq!(snapshot.objects.len(), 4);
+        assert!(snapshot.find(garbage.erase()).is_none());
+
+        let c = snapshot.find(c.erase()).unwrap();
+        let path: Vec<_> = snapshot.retained_by(c)
+            .into_iter()
+            .map(|index| snapshot.objects[index].object)
+            .collect();
+        assert!(path == [a.erase(), b.erase(), snapshot.objects[c].object]);
+        assert!(snapshot.objects[c].type_name.ends_with("Node"));
+
+        // Only objects that reference `b` count when it is also a root.
+        let snapshot = heap.snapshot(&[&b, &a], Some(b.erase()));
+        let b = snapshot.find(b.erase()).unwrap();
+        assert!(snapshot.objects[b].retainer.is_some());
+    }
+
+    #[test]
+    fn stress() {
+        let heap = heap();
+        heap.set_stress(true);
+
+        let rooted = node(&heap, None);
+        unsafe {
+            heap.add_root(&rooted as &dyn Trace);
+        }
+
+        let garbage = heap.manage(Cell::new(1234u32));
+
+        // Collects `garbage` and poisons it. The allocation is in another size class, so it is not reused.
+        unsafe {
+            heap.alloc(std::alloc::Layout::new::<[u8; 1024]>());
+        }
+
+        assert!(is_marked(&heap, rooted));
+        assert_eq!(garbage.get(), 0xA5A5A5A5);
+
+        heap.remove_root(&rooted as &dyn Trace);
+        assert!(heap.roots.borrow().is_empty());
+    }
+
+    #[test]
+    fn root() {
+        // Roots register with the thread's heap.
+        crate::set_stress(false);
+
+        let a = crate::manage(Node {
+            next: Cell::new(None),
+        });
+        let weak = crate::downgrade(a);
+
+        let root = crate::Root::new(a);
+        let copy = root.clone();
+        drop(root);
+
+        crate::force_collect(&[&weak]);
+        assert!(weak.upgrade() == Some(copy.get()));
+
+        drop(copy);
+        crate::force_collect(&[&weak]);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn incremental_sweeps_garbage() {
+        let heap = heap();
+        heap.set_collector(Collector::Incremental);
+
+        let a = node(&heap, None);
+        let garbage = node(&heap, None);
+
+        heap.start(&[&a]);
+        heap.collect(&[&a]);
+        assert!(!heap.is_marking());
+        assert!(is_marked(&heap, a));
+        assert!(!is_marked(&heap, garbage));
+    }
 }
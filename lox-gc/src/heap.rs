@@ -1,8 +1,7 @@
 use std::cell::Cell;
+use std::io;
 use lox_mmap::MemoryMap;
 
-//TODO Merge reams when sweeping
-
 /// Returns the number of pages needed for `n` bytes (rounding up).
 const fn bytes_to_pages(n: usize) -> usize {
   if n % AddrSpace::PAGE_BYTES != 0 {
@@ -16,9 +15,45 @@ const fn bytes_to_pages(n: usize) -> usize {
 #[repr(transparent)]
 struct PdIdx(u32);
 
+/// How much address space a heap reserves, and how it is backed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HeapConfig {
+    /// The most bytes the heap can ever hold. This is only reserved, pages are backed once used.
+    /// Page descriptors and bitmaps are reserved on top of this.
+    pub reserve_bytes: usize,
+    /// Back the heap with huge pages, falling back to normal pages if they are not available.
+    pub huge_pages: bool,
+    /// After a collection, runs of empty pages at least this large are given back to the OS.
+    /// Use `usize::MAX` to keep every page.
+    pub release_bytes: usize,
+}
+
+impl HeapConfig {
+    pub const DEFAULT_RESERVE_BYTES: usize = 4 * 1024 * 1024 * 1024; // 4G
+    pub const DEFAULT_RELEASE_BYTES: usize = 1024 * 1024; // 1M
+
+    /// The smallest reservation that leaves room for objects next to the heap's own lists.
+    pub const MIN_RESERVE_BYTES: usize = 64 * AddrSpace::PAGE_BYTES;
+}
+
+impl Default for HeapConfig {
+    fn default() -> Self {
+        Self {
+            reserve_bytes: Self::DEFAULT_RESERVE_BYTES,
+            huge_pages: false,
+            release_bytes: Self::DEFAULT_RELEASE_BYTES,
+        }
+    }
+}
+
 struct AddrSpace {
     mem: MemoryMap,
 
+    data_pages: usize,
+    pd_bytes: usize,
+    marks_start: usize,
+    data_start: usize,
+
     used_pds: Cell<u32>,
     reserved_pds: Cell<u32>,
 }
@@ -26,28 +61,37 @@ struct AddrSpace {
 impl AddrSpace {
     const PAGE_BYTES: usize = 4096;
 
-    const DATA_BYTES: usize = 4 * 1024 * 1024 * 1024; // 4G
-    //const DATA_BYTES: usize = 64 * 1024 * 1024; // 64MB
-    //const DATA_BYTES: usize = 32 * 1024 * 1024; // 32MB
-    const DATA_PAGES: usize = Self::DATA_BYTES / Self::PAGE_BYTES;
-
-    const PD_PAGES: usize = bytes_to_pages(Self::DATA_PAGES * std::mem::size_of::<PageDescriptor>());
-    const BITMAP_PAGES: usize = bytes_to_pages(Self::DATA_PAGES * std::mem::size_of::<Bitmap>());
+    /// Constructs a new [`AddrSpace`] with room for `config.reserve_bytes` of data.
+    pub fn create(config: HeapConfig) -> io::Result<Self> {
+        if config.reserve_bytes < HeapConfig::MIN_RESERVE_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "heap reservation is too small"));
+        }
 
-    const PD_BYTES: usize = Self::PD_PAGES * Self::PAGE_BYTES;
-    const BITMAP_BYTES: usize = Self::BITMAP_PAGES * Self::PAGE_BYTES;
+        let data_pages = bytes_to_pages(config.reserve_bytes);
+        if data_pages > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "heap reservation is too large"));
+        }
 
-    const TOTAL_PAGES: usize = Self::DATA_PAGES + Self::PD_PAGES + Self::BITMAP_PAGES;
-    const TOTAL_BYTES: usize = Self::TOTAL_PAGES * Self::PAGE_BYTES;
+        let pd_bytes = bytes_to_pages(data_pages * std::mem::size_of::<PageDescriptor>()) * Self::PAGE_BYTES;
+        let bitmap_bytes = bytes_to_pages(data_pages * std::mem::size_of::<Bitmap>()) * Self::PAGE_BYTES;
 
-    const DATA_START: usize = Self::PD_BYTES + Self::BITMAP_BYTES;
+        // One bitmap for allocated blocks and one for marked blocks.
+        let marks_start = pd_bytes + bitmap_bytes;
+        let data_start = pd_bytes + 2 * bitmap_bytes;
+        let total_bytes = data_start + data_pages * Self::PAGE_BYTES;
 
-    /// Constructs a new [`AddrSpace`]. This will return `None` on error.
-    pub fn create() -> Option<Self> {
-        let mem = self::MemoryMap::new(Self::TOTAL_BYTES);
+        let mem = if config.huge_pages {
+            MemoryMap::new_huge(total_bytes).or_else(|_| MemoryMap::new(total_bytes))?
+        } else {
+            MemoryMap::new(total_bytes)?
+        };
 
-        Some(Self {
+        Ok(Self {
             mem,
+            data_pages,
+            pd_bytes,
+            marks_start,
+            data_start,
             used_pds: Cell::new(0),
             reserved_pds: Cell::new(0),
         })
@@ -71,12 +115,20 @@ impl AddrSpace {
 
     /// Constructs a [`PdRef`] for the page `ptr` is in.
     pub unsafe fn pd_of(&self, ptr: *const u8) -> PdRef {
-        let base = self.mem.data().add(Self::DATA_START);
+        let base = self.mem.data().add(self.data_start);
         let offset = ptr.offset_from(base as _) as usize;
         let index = offset / Self::PAGE_BYTES;
         self.pd_at(index as _)
     }
 
+    /// Give the pages of the free ream `pd` back to the OS.
+    pub fn release(&self, pd: PdRef) -> io::Result<()> {
+        let offset = self.data_start + pd.idx.0 as usize * Self::PAGE_BYTES;
+        unsafe {
+            self.mem.release(offset, pd.pages() * Self::PAGE_BYTES)
+        }
+    }
+
     /// Return an iterator over all created [`PageDescriptor`]s.
     pub fn pds(&self) -> impl Iterator<Item = PdRef> {
         let end = self.used_pds.get();
@@ -98,7 +150,7 @@ impl AddrSpace {
 
     pub fn available_pages(&self) -> u32 {
         let used_pages = self.used_pds.get();
-        Self::DATA_PAGES as u32 - used_pages
+        self.data_pages as u32 - used_pages
     }
 
     pub(crate) fn new_reserved(&self) -> PdList {
@@ -114,7 +166,7 @@ impl AddrSpace {
         let used_pages = self.used_pds.get();
 
         debug_assert!(count > 0);
-        debug_assert!((used_pages + count) as usize <= Self::DATA_PAGES);
+        debug_assert!((used_pages + count) as usize <= self.data_pages);
 
         self.used_pds.set(used_pages+count);
 
@@ -122,7 +174,10 @@ impl AddrSpace {
             let ptr = self.mem.data().cast::<PageDescriptor>().add(used_pages as _);
             ptr.write_bytes(0, count as _);
 
-            let ptr = self.mem.data().add(Self::PD_BYTES).cast::<Bitmap>().add(used_pages as _);
+            let ptr = self.mem.data().add(self.pd_bytes).cast::<Bitmap>().add(used_pages as _);
+            ptr.write_bytes(0, count as _);
+
+            let ptr = self.mem.data().add(self.marks_start).cast::<Bitmap>().add(used_pages as _);
             ptr.write_bytes(0, count as _);
         }
 
@@ -188,6 +243,9 @@ struct PageDescriptor {
 
     class: Cell<SizeClass>,
 
+    /// None of these pages are backed by physical memory. Only set on free pages.
+    released: Cell<bool>,
+
     /// Additional pages
     len: Cell<u32>,
 }
@@ -207,9 +265,19 @@ impl<'space> PdRef<'space> {
         }
     }
 
+    /// The blocks that are allocated.
     fn bitmap(self) -> &'space Bitmap {
         unsafe {
-            let offset = self.idx.0 as usize * std::mem::size_of::<Bitmap>() + AddrSpace::PD_BYTES;
+            let offset = self.idx.0 as usize * std::mem::size_of::<Bitmap>() + self.space.pd_bytes;
+            let ptr = self.space.mem.data().add(offset).cast::<Bitmap>();
+            &*ptr
+        }
+    }
+
+    /// The blocks that are marked. Allocating a block also marks it.
+    fn marks(self) -> &'space Bitmap {
+        unsafe {
+            let offset = self.idx.0 as usize * std::mem::size_of::<Bitmap>() + self.space.marks_start;
             let ptr = self.space.mem.data().add(offset).cast::<Bitmap>();
             &*ptr
         }
@@ -231,6 +299,14 @@ impl<'space> PdRef<'space> {
         self.pd().class.get()
     }
 
+    pub fn set_released(self, released: bool) {
+        self.pd().released.set(released)
+    }
+
+    pub fn is_released(self) -> bool {
+        self.pd().released.get()
+    }
+
     pub fn is_linked(self) -> bool {
         self.pd().prev.get() != self.idx || self.pd().next.get() != self.idx
     }
@@ -240,6 +316,22 @@ impl<'space> PdRef<'space> {
         for word in self.bitmap().0.iter() {
             word.set(0)
         }
+
+        self.clear_marks();
+    }
+
+    //TODO Move to bitmap
+    pub fn clear_marks(self) {
+        for word in self.marks().0.iter() {
+            word.set(0)
+        }
+    }
+
+    /// Free every allocated block that is not marked.
+    pub fn free_unmarked(self) {
+        for (word, marks) in self.bitmap().0.iter().zip(self.marks().0.iter()) {
+            word.set(word.get() & marks.get());
+        }
     }
 
     //TODO Move to bitmap
@@ -292,7 +384,7 @@ impl<'space> PdRef<'space> {
     }
 
     pub fn data(self, index: usize) -> *mut u8 {
-        let offset = AddrSpace::DATA_START
+        let offset = self.space.data_start
             + self.idx.0 as usize * AddrSpace::PAGE_BYTES
             + index * self.class().block_bytes().unwrap_or(1);
 
@@ -307,11 +399,11 @@ impl<'space> PdRef<'space> {
         let word = index / 64;
         let bit = index % 64;
 
-        let word = &self.bitmap().0[word];
+        let word = &self.marks().0[word];
         word.set(word.get() | (1 << bit));
     }
 
-    pub fn is_marked(self, index: usize) -> bool {
+    pub fn is_allocated(self, index: usize) -> bool {
         debug_assert!(index < self.class().total_blocks());
 
         let word = index / 64;
@@ -321,6 +413,16 @@ impl<'space> PdRef<'space> {
         word.get() & (1 << bit) != 0
     }
 
+    pub fn is_marked(self, index: usize) -> bool {
+        debug_assert!(index < self.class().total_blocks());
+
+        let word = index / 64;
+        let bit = index % 64;
+
+        let word = &self.marks().0[word];
+        word.get() & (1 << bit) != 0
+    }
+
     pub fn next(self) -> PdRef<'space> {
         PdRef {
             idx: self.pd().next.get(),
@@ -396,12 +498,21 @@ impl<'space> PdRef<'space> {
             };
             unsafe { rest.force_unlink() };
             rest.pd().len.set(rest_len as _);
+            rest.set_released(self.is_released());
 
             self.pd().len.set(split as u32 - 1);
 
             Some((self, Some(rest)))
         }
     }
+
+    /// Grow `self` over `next`, which must be free and directly follow it.
+    pub fn absorb(self, next: Self) {
+        debug_assert_eq!(self.idx.0 as usize + self.pages(), next.idx.0 as usize);
+
+        next.unlink();
+        self.pd().len.set(self.pd().len.get() + next.pages() as u32);
+    }
 }
 
 struct Bitmap([Cell<u64>; 4]);
@@ -464,6 +575,14 @@ impl PdList {
     }
 }
 
+/// Live bytes in one size class, as of the last sweep plus allocations since.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClassStats {
+    /// The block size, or `None` for reams (allocations larger than a page).
+    pub block_bytes: Option<usize>,
+    pub live_bytes: usize,
+}
+
 pub struct Heap {
     space: AddrSpace,
 
@@ -475,11 +594,16 @@ pub struct Heap {
     full_pages: PdList,
 
     bytes_used: Cell<usize>,
+    bytes_allocated: Cell<usize>,
+    bytes_freed: Cell<usize>,
+    bytes_released: Cell<usize>,
+
+    release_bytes: usize,
 }
 
 impl Heap {
-    pub fn new() -> Option<Heap> {
-        let space = AddrSpace::create()?;
+    pub fn new(config: HeapConfig) -> io::Result<Heap> {
+        let space = AddrSpace::create(config)?;
 
         let heap = Self {
             free_reams: space.new_reserved(),
@@ -500,18 +624,60 @@ impl Heap {
             full_pages: space.new_reserved(),
             space,
             bytes_used: Cell::new(0),
+            bytes_allocated: Cell::new(0),
+            bytes_freed: Cell::new(0),
+            bytes_released: Cell::new(0),
+            release_bytes: config.release_bytes,
         };
 
         let ream = heap.space.new_ream(heap.space.available_pages());
+        // Nothing has touched these pages yet.
+        ream.set_released(true);
         heap.free_reams.push(ream);
 
-        Some(heap)
+        Ok(heap)
     }
 
     pub fn bytes_used(&self) -> usize {
         self.bytes_used.get()
     }
 
+    /// Total bytes ever allocated, rounded up to the size class.
+    pub fn bytes_allocated(&self) -> usize {
+        self.bytes_allocated.get()
+    }
+
+    /// Total bytes ever freed by sweeping.
+    pub fn bytes_freed(&self) -> usize {
+        self.bytes_freed.get()
+    }
+
+    /// Total bytes of empty pages ever given back to the OS.
+    pub fn bytes_released(&self) -> usize {
+        self.bytes_released.get()
+    }
+
+    pub fn class_stats(&self) -> Vec<ClassStats> {
+        let mut stats: Vec<_> = std::iter::once(SizeClass::Ream)
+            .chain(SizeClass::SMALL)
+            .map(|class| ClassStats {
+                block_bytes: class.block_bytes(),
+                live_bytes: 0,
+            })
+            .collect();
+
+        for pd in self.space.pds() {
+            let index = match pd.class() {
+                SizeClass::Ream => 0,
+                class => class as usize - 3, //TODO constant
+            };
+
+            stats[index].live_bytes += pd.bytes_used() as usize;
+        }
+
+        stats
+    }
+
     pub fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
         let bytes = layout.size();
         if bytes <= AddrSpace::PAGE_BYTES {
@@ -525,7 +691,7 @@ impl Heap {
 
     pub unsafe fn start_gc(&self) {
         self.space.pds()
-            .for_each(|pd| pd.set_empty());
+            .for_each(|pd| pd.clear_marks());
     }
 
     pub unsafe fn mark(&self, ptr: *const u8) {
@@ -546,9 +712,32 @@ impl Heap {
         }
     }
 
+    /// The size of the block `ptr` points to the start of.
+    pub fn block_bytes(&self, ptr: *const u8) -> usize {
+        let page = unsafe { self.space.pd_of(ptr) };
+        page.class().block_bytes().unwrap_or(page.pages() * AddrSpace::PAGE_BYTES)
+    }
+
+    /// Overwrite every block the next sweep will free, so stale pointers into them stand out.
+    pub unsafe fn poison_unmarked(&self) {
+        const POISON: u8 = 0xA5;
+
+        for pd in self.space.pds() {
+            let class = pd.class();
+            let block_bytes = class.block_bytes().unwrap_or(pd.pages() * AddrSpace::PAGE_BYTES);
+
+            for index in 0..class.total_blocks() {
+                if pd.is_allocated(index) && !pd.is_marked(index) {
+                    pd.data(index).write_bytes(POISON, block_bytes);
+                }
+            }
+        }
+    }
+
     pub unsafe fn sweep(&self) {
         let mut count = 0;
         for pd in self.space.pds() {
+            pd.free_unmarked();
             count += pd.bytes_used() as usize;
 
             if pd.is_empty() {
@@ -566,7 +755,58 @@ impl Heap {
                 self.sized_list(pd.class()).push(pd);
             }
         }
+        self.bytes_freed.set(self.bytes_freed.get() + self.bytes_used.get() - count);
         self.bytes_used.set(count);
+
+        self.coalesce();
+    }
+
+    /// Merge every run of adjacent empty pages into one free ream,
+    /// and give the large runs back to the OS.
+    fn coalesce(&self) {
+        // The first page of the current run, and how many of its pages may still be backed.
+        let mut run: Option<(PdRef, usize)> = None;
+
+        for pd in self.space.pds() {
+            if !pd.is_empty() {
+                if let Some((head, backed)) = run.take() {
+                    self.finish_run(head, backed);
+                }
+                continue;
+            }
+
+            let backed = if pd.is_released() { 0 } else { pd.pages() };
+
+            run = match run {
+                Some((head, head_backed)) => {
+                    head.absorb(pd);
+                    Some((head, head_backed + backed))
+                },
+                None => Some((pd, backed)),
+            };
+        }
+
+        if let Some((head, backed)) = run {
+            self.finish_run(head, backed);
+        }
+    }
+
+    fn finish_run(&self, head: PdRef, backed: usize) {
+        if !head.is_single_page() {
+            head.unlink();
+            self.free_reams.push(head);
+        }
+
+        let released = backed == 0 || (
+            head.pages() * AddrSpace::PAGE_BYTES >= self.release_bytes
+            && self.space.release(head).is_ok()
+        );
+
+        if released {
+            self.bytes_released.set(self.bytes_released.get() + backed * AddrSpace::PAGE_BYTES);
+        }
+
+        head.set_released(released);
     }
 
     fn sized_list(&self, size_class: SizeClass) -> &PdList {
@@ -597,6 +837,7 @@ impl Heap {
             };
 
             ream.unlink();
+            ream.set_released(false);
             return ream;
         }
 
@@ -605,10 +846,13 @@ impl Heap {
 
     fn alloc_ream(&self, pages: u32) -> *mut u8 {
         self.bytes_used.set(self.bytes_used.get() + pages as usize * AddrSpace::PAGE_BYTES);
+        self.bytes_allocated.set(self.bytes_allocated.get() + pages as usize * AddrSpace::PAGE_BYTES);
         let page = self.take_ream(pages);
 
         page.set_class(SizeClass::Ream);
-        page.take_next_block();
+        page.clear_marks();
+        let index = page.take_next_block().expect("Ream already in use");
+        page.mark(index);
         self.full_reams.push(page);
 
         page.data(0)
@@ -616,6 +860,7 @@ impl Heap {
 
     fn alloc_small(&self, size_class: SizeClass) -> *mut u8 {
         self.bytes_used.set(self.bytes_used.get() + size_class.block_bytes().unwrap());
+        self.bytes_allocated.set(self.bytes_allocated.get() + size_class.block_bytes().unwrap());
 
         let list = self.sized_list(size_class);
 
@@ -625,6 +870,7 @@ impl Heap {
                 let page = match self.free_pages.first(&self.space) {
                     Some(page) => {
                         page.unlink();
+                        page.set_released(false);
                         page
                     },
                     None => self.take_ream(1),
@@ -638,6 +884,7 @@ impl Heap {
         };
 
         let index = page.take_next_block().expect("Full page in free list");
+        page.mark(index);
 
         if page.is_full() {
             page.unlink();
@@ -654,7 +901,7 @@ mod tests {
 
     #[test]
     pub fn it_works() {
-        let heap = Heap::new().unwrap();
+        let heap = Heap::new(HeapConfig::default()).unwrap();
 
         let layout = std::alloc::Layout::new::<u64>();
         let _ptr = heap.alloc(layout) as *mut u64;
@@ -678,4 +925,83 @@ mod tests {
         let _ptr2 = heap.alloc(layout) as *mut u64;
         assert!(_ptr == _ptr2);
     }
+
+    #[test]
+    pub fn reservation() {
+        let config = HeapConfig {
+            reserve_bytes: HeapConfig::MIN_RESERVE_BYTES,
+            huge_pages: false,
+            ..HeapConfig::default()
+        };
+        let heap = Heap::new(config).unwrap();
+        let ptr = heap.alloc(std::alloc::Layout::new::<[u8; 8192]>());
+        assert!(!ptr.is_null());
+
+        let too_small = HeapConfig {
+            reserve_bytes: HeapConfig::MIN_RESERVE_BYTES - 1,
+            huge_pages: false,
+            ..HeapConfig::default()
+        };
+        assert_eq!(Heap::new(too_small).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+
+        let too_large = HeapConfig {
+            reserve_bytes: (u32::MAX as usize + 1) * AddrSpace::PAGE_BYTES,
+            huge_pages: false,
+            ..HeapConfig::default()
+        };
+        assert_eq!(Heap::new(too_large).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+
+        // Falls back to normal pages when no huge pages are configured.
+        let huge = HeapConfig {
+            reserve_bytes: HeapConfig::MIN_RESERVE_BYTES,
+            huge_pages: true,
+            ..HeapConfig::default()
+        };
+        assert!(Heap::new(huge).is_ok());
+    }
+
+    #[test]
+    pub fn coalesce_and_release() {
+        let config = HeapConfig {
+            reserve_bytes: HeapConfig::MIN_RESERVE_BYTES,
+            release_bytes: 8 * AddrSpace::PAGE_BYTES,
+            ..HeapConfig::default()
+        };
+        let heap = Heap::new(config).unwrap();
+
+        // A live small block, followed by 16 pages that each hold a single block.
+        let small = heap.alloc(std::alloc::Layout::new::<u64>());
+        let page = std::alloc::Layout::new::<[u8; AddrSpace::PAGE_BYTES]>();
+        let pages: Vec<_> = (0..16).map(|_| heap.alloc(page)).collect();
+        unsafe {
+            pages[0].write_bytes(0xFF, AddrSpace::PAGE_BYTES);
+            heap.start_gc();
+            heap.mark(small);
+            heap.sweep();
+        }
+
+        // The freed pages and the rest of the heap form one run, which is released.
+        assert_eq!(heap.bytes_released(), 16 * AddrSpace::PAGE_BYTES);
+        assert_eq!(heap.free_reams.iter(&heap.space).count(), 1);
+        assert_eq!(heap.free_pages.iter(&heap.space).count(), 0);
+
+        // A ream that spans all the freed pages fits where they were, and reads as zero.
+        let ream = heap.alloc(std::alloc::Layout::new::<[u8; 16 * AddrSpace::PAGE_BYTES]>());
+        assert_eq!(ream, pages[0]);
+        assert_eq!(unsafe { ream.read() }, 0);
+
+        // Released pages are not counted again.
+        unsafe {
+            heap.start_gc();
+            heap.mark(small);
+            heap.sweep();
+        }
+        assert_eq!(heap.bytes_released(), 32 * AddrSpace::PAGE_BYTES);
+        unsafe {
+            heap.start_gc();
+            heap.mark(small);
+            heap.sweep();
+        }
+        assert_eq!(heap.bytes_released(), 32 * AddrSpace::PAGE_BYTES);
+    }
 }
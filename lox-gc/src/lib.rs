@@ -1,27 +1,205 @@
+// So `#[derive(Trace)]` also works inside this crate.
+extern crate self as lox_gc;
+
 mod heap;
 mod gc;
+mod root;
+mod snapshot;
+
+use std::cell::OnceCell;
+use std::io;
 
 use gc::ManagedHeap;
-pub use gc::{Gc, Trace, Tracer};
+pub use gc::{Collector, Gc, Stats, Trace, Tracer, Weak};
+pub use lox_gc_derive::Trace;
+pub use heap::{ClassStats, HeapConfig};
+pub use root::Root;
+pub use snapshot::{Snapshot, SnapshotObject};
 
 thread_local! {
-    pub static HEAP: ManagedHeap = ManagedHeap::new();
+    static HEAP: OnceCell<ManagedHeap> = const { OnceCell::new() };
 }
 
-pub fn manage<T>(data: T) -> Gc<T> where T: Trace + 'static {
+/// Create the current thread's heap with `config`, unless it already exists.
+/// Otherwise the heap is created with [`HeapConfig::default`] on first use.
+pub fn init(config: HeapConfig) -> io::Result<()> {
+    HEAP.with(|heap| {
+        if heap.get().is_none() {
+            let _ = heap.set(ManagedHeap::new(config)?);
+        }
+
+        Ok(())
+    })
+}
+
+/// Whether the current thread's heap has been created yet.
+pub fn is_initialized() -> bool {
+    HEAP.with(|heap| heap.get().is_some())
+}
+
+#[inline]
+fn with_heap<R>(f: impl FnOnce(&ManagedHeap) -> R) -> R {
     HEAP.with(|heap| {
+        let heap = heap.get_or_init(|| {
+            ManagedHeap::new(HeapConfig::default()).expect("Failed to reserve the heap")
+        });
+
+        f(heap)
+    })
+}
+
+pub fn manage<T>(data: T) -> Gc<T> where T: Trace + 'static {
+    with_heap(|heap| {
         heap.manage(data)
     })
 }
 
 pub unsafe fn alloc(layout: std::alloc::Layout) -> *mut u8 {
-    HEAP.with(|heap| {
+    with_heap(|heap| {
         heap.alloc(layout)
     })
 }
 
 pub fn collect(roots: &[&dyn Trace]) {
-    HEAP.with(|heap| {
+    with_heap(|heap| {
         heap.collect(roots)
     })
 }
+
+pub fn downgrade<T: ?Sized>(gc: Gc<T>) -> Weak<T> {
+    with_heap(|heap| {
+        heap.downgrade(gc)
+    })
+}
+
+/// The number of completed collection cycles.
+pub fn collections() -> usize {
+    with_heap(|heap| {
+        heap.collections()
+    })
+}
+
+/// Collect now, regardless of the threshold.
+pub fn force_collect(roots: &[&dyn Trace]) {
+    with_heap(|heap| {
+        heap.force_collect(roots)
+    })
+}
+
+/// Record every object reachable from `roots` and the registered roots, without collecting.
+pub fn snapshot(roots: &[&dyn Trace]) -> Snapshot {
+    with_heap(|heap| {
+        heap.snapshot(roots, None)
+    })
+}
+
+/// Like [`snapshot`], but `object` is only recorded if another object references it.
+/// Use this to find what retains an object that is also on the stack.
+pub fn snapshot_retaining(roots: &[&dyn Trace], object: Gc<()>) -> Snapshot {
+    with_heap(|heap| {
+        heap.snapshot(roots, Some(object))
+    })
+}
+
+pub fn stats() -> Stats {
+    with_heap(|heap| {
+        heap.stats()
+    })
+}
+
+pub fn set_threshold(bytes: usize) {
+    with_heap(|heap| {
+        heap.set_threshold(bytes)
+    })
+}
+
+pub fn set_growth_factor(factor: f32) {
+    with_heap(|heap| {
+        heap.set_growth_factor(factor)
+    })
+}
+
+/// Force a full collection on every allocation and poison swept memory, so missing roots
+/// fail deterministically. Returns the previous setting. Enabled by default with the `stress` feature.
+///
+/// These collections only trace registered roots and the value being managed.
+pub fn set_stress(enabled: bool) -> bool {
+    with_heap(|heap| {
+        heap.set_stress(enabled)
+    })
+}
+
+pub fn is_stress() -> bool {
+    with_heap(|heap| {
+        heap.is_stress()
+    })
+}
+
+/// Trace `root` on every collection until it is removed again.
+///
+/// # Safety
+/// `root` must stay valid until it is passed to [`remove_root`]. [`Root`] does this for you.
+pub unsafe fn add_root(root: *const dyn Trace) {
+    with_heap(|heap| {
+        heap.add_root(root)
+    })
+}
+
+pub fn remove_root(root: *const dyn Trace) {
+    // Roots held in thread locals may be dropped after the heap is.
+    let _ = HEAP.try_with(|heap| {
+        if let Some(heap) = heap.get() {
+            heap.remove_root(root);
+        }
+    });
+}
+
+/// Keep everything `root` references alive while running `f`.
+/// Use this for objects that are not reachable from a root yet while `f` allocates.
+pub fn with_root<T: Trace, R>(root: &T, f: impl FnOnce() -> R) -> R {
+    struct Guard(*const dyn Trace);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            remove_root(self.0);
+        }
+    }
+
+    let root = root as &dyn Trace as *const dyn Trace;
+    // The guard removes the root before `root` goes out of scope, so extending the lifetime is fine.
+    let root: *const (dyn Trace + 'static) = unsafe { std::mem::transmute(root) };
+    unsafe {
+        add_root(root);
+    }
+    let _guard = Guard(root);
+
+    f()
+}
+
+pub fn collector() -> Collector {
+    with_heap(|heap| {
+        heap.collector()
+    })
+}
+
+/// Whether an incremental mark is in progress.
+pub fn is_marking() -> bool {
+    with_heap(|heap| {
+        heap.is_marking()
+    })
+}
+
+pub fn set_collector(collector: Collector) {
+    with_heap(|heap| {
+        heap.set_collector(collector)
+    })
+}
+
+/// Must be called with every reference stored into an existing heap object,
+/// so the incremental collector does not miss it.
+#[inline]
+pub fn write_barrier<T: Trace + ?Sized>(value: &T) {
+    with_heap(|heap| {
+        heap.write_barrier(value)
+    })
+}
@@ -0,0 +1,66 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use crate::Trace;
+
+/// Keeps everything `T` references alive until it is dropped,
+/// for values held outside the heap, such as a callback stored by the host.
+///
+/// The heap is per thread, so a root can not be sent to another thread.
+pub struct Root<T: Trace + 'static> {
+    // Boxed so its address can be registered as a root.
+    value: Box<T>,
+    marker: PhantomData<*const ()>,
+}
+
+impl<T: Trace + 'static> Root<T> {
+    pub fn new(value: T) -> Self {
+        let value = Box::new(value);
+        unsafe {
+            crate::add_root(&*value as *const T);
+        }
+
+        Self {
+            value,
+            marker: PhantomData,
+        }
+    }
+
+    /// Replace the rooted value.
+    pub fn set(&mut self, value: T) {
+        // Registered roots are scanned again before the sweep, so no write barrier is needed.
+        *self.value = value;
+    }
+}
+
+impl<T: Trace + Copy + 'static> Root<T> {
+    pub fn get(&self) -> T {
+        *self.value
+    }
+}
+
+impl<T: Trace + 'static> Deref for Root<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Trace + Clone + 'static> Clone for Root<T> {
+    fn clone(&self) -> Self {
+        Self::new((*self.value).clone())
+    }
+}
+
+impl<T: Trace + 'static> Drop for Root<T> {
+    fn drop(&mut self) {
+        crate::remove_root(&*self.value as *const T);
+    }
+}
+
+impl<T: Trace + std::fmt::Debug + 'static> std::fmt::Debug for Root<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Root").field(&self.value).finish()
+    }
+}
@@ -0,0 +1,107 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+
+use crate::Gc;
+
+/// Every object reachable from the roots, in the order they were first reached.
+pub struct Snapshot {
+    pub objects: Vec<SnapshotObject>,
+    index: HashMap<u64, usize>,
+}
+
+/// The `object` in a [`SnapshotObject`] is not a root,
+/// so it must not be used once the heap has collected again.
+pub struct SnapshotObject {
+    pub object: Gc<()>,
+    pub type_name: &'static str,
+    /// The object's own block, plus the buffers it owns.
+    pub bytes: usize,
+    /// The object that first reached this one, or `None` if a root did.
+    pub retainer: Option<usize>,
+}
+
+impl Snapshot {
+    /// The index of `object` in [`Snapshot::objects`], if it is reachable.
+    pub fn find(&self, object: Gc<()>) -> Option<usize> {
+        self.index.get(&object.to_bits()).copied()
+    }
+
+    /// The chain of objects that keeps the object at `index` alive, starting at a root.
+    /// Objects are reached breadth first, so this is a shortest chain.
+    pub fn retained_by(&self, index: usize) -> Vec<usize> {
+        let mut path = vec![index];
+        while let Some(retainer) = self.objects[*path.last().unwrap()].retainer {
+            path.push(retainer);
+        }
+
+        path.reverse();
+        path
+    }
+}
+
+/// Collects a [`Snapshot`] while tracing, instead of marking.
+#[derive(Default)]
+pub(crate) struct Census {
+    objects: RefCell<Vec<SnapshotObject>>,
+    index: RefCell<HashMap<u64, usize>>,
+    buffers: RefCell<HashSet<*const u8>>,
+    /// The object being traced, `None` while tracing the roots.
+    current: Cell<Option<usize>>,
+    /// An object that is only recorded once another object references it.
+    skip_root: Option<u64>,
+}
+
+impl Census {
+    pub fn new(skip_root: Option<Gc<()>>) -> Self {
+        Self {
+            skip_root: skip_root.map(Gc::to_bits),
+            ..Default::default()
+        }
+    }
+
+    pub fn visit(&self, object: Gc<()>, type_name: &'static str, bytes: usize) {
+        if self.current.get().is_none() && self.skip_root == Some(object.to_bits()) {
+            return;
+        }
+
+        let mut index = self.index.borrow_mut();
+        if index.contains_key(&object.to_bits()) {
+            return;
+        }
+
+        let mut objects = self.objects.borrow_mut();
+        index.insert(object.to_bits(), objects.len());
+        objects.push(SnapshotObject {
+            object,
+            type_name,
+            bytes,
+            retainer: self.current.get(),
+        });
+    }
+
+    /// Count a buffer towards the object being traced.
+    pub fn add_buffer(&self, ptr: *const u8, bytes: usize) {
+        if !self.buffers.borrow_mut().insert(ptr) {
+            return;
+        }
+
+        if let Some(current) = self.current.get() {
+            self.objects.borrow_mut()[current].bytes += bytes;
+        }
+    }
+
+    pub fn object(&self, index: usize) -> Option<Gc<()>> {
+        self.objects.borrow().get(index).map(|object| object.object)
+    }
+
+    pub fn set_current(&self, index: usize) {
+        self.current.set(Some(index));
+    }
+
+    pub fn into_snapshot(self) -> Snapshot {
+        Snapshot {
+            objects: self.objects.into_inner(),
+            index: self.index.into_inner(),
+        }
+    }
+}
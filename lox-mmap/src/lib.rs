@@ -1,10 +1,8 @@
+use std::io;
 use std::ptr;
 
 //TODO support windows (using 'windows' crate)
 //TODO consider replacing *mut u8 with NonNull
-//TODO use libc::MAP_HUGETLB on supported platforms
-//TODO check errno on failure and report correctly
-//TODO return Result instead of panicking
 //TODO Split into a module per platform
 
 pub struct MemoryMap {
@@ -13,25 +11,43 @@ pub struct MemoryMap {
 }
 
 impl MemoryMap {
+    /// The huge page size assumed when rounding up huge mappings.
+    pub const HUGE_PAGE_BYTES: usize = 2 * 1024 * 1024;
+
     pub fn data(&self) -> *mut u8 {
         self.data
     }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
 }
 
 #[cfg(miri)]
 impl MemoryMap {
-    pub fn new(size: usize) -> Self {
-        unsafe {
-            let layout = std::alloc::Layout::array::<u8>(size)
-                .unwrap()
-                .align_to(4096)
-                .unwrap();
-            let data = std::alloc::alloc(layout);
+    pub fn new(size: usize) -> io::Result<Self> {
+        let layout = std::alloc::Layout::array::<u8>(size)
+            .and_then(|layout| layout.align_to(4096))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
 
-            Self { size, data }
+        let data = unsafe { std::alloc::alloc(layout) };
+        if data.is_null() {
+            return Err(io::ErrorKind::OutOfMemory.into());
         }
+
+        Ok(Self { size, data })
     }
 
+    pub fn new_huge(_size: usize) -> io::Result<Self> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// # Safety
+    /// `offset..offset + len` must be inside the mapping.
+    pub unsafe fn release(&self, offset: usize, len: usize) -> io::Result<()> {
+        self.data.add(offset).write_bytes(0, len);
+        Ok(())
+    }
 }
 
 #[cfg(miri)]
@@ -49,24 +65,63 @@ impl Drop for MemoryMap {
 
 #[cfg(all(not(miri), unix))]
 impl MemoryMap {
-    pub fn new(size: usize) -> Self {
+    /// Reserves `size` bytes of zeroed memory. Pages are only backed once they are touched.
+    pub fn new(size: usize) -> io::Result<Self> {
+        Self::map(size, libc::MAP_NORESERVE)
+    }
+
+    /// Reserves `size` bytes backed by huge pages, rounded up to [`MemoryMap::HUGE_PAGE_BYTES`].
+    ///
+    /// Huge pages come from the pool configured in `/proc/sys/vm/nr_hugepages`,
+    /// and the whole mapping is reserved from it up front.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn new_huge(size: usize) -> io::Result<Self> {
+        let size = size
+            .checked_next_multiple_of(Self::HUGE_PAGE_BYTES)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+
+        // Without MAP_NORESERVE a pool that is too small fails here, instead of with SIGBUS later.
+        Self::map(size, libc::MAP_HUGETLB)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn new_huge(_size: usize) -> io::Result<Self> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Give the physical pages behind `offset..offset + len` back to the OS.
+    /// They read as zero when touched again.
+    ///
+    /// # Safety
+    /// `offset..offset + len` must be inside the mapping and page aligned.
+    pub unsafe fn release(&self, offset: usize, len: usize) -> io::Result<()> {
+        if libc::madvise(self.data.add(offset) as _, len, libc::MADV_DONTNEED) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn map(size: usize, flags: libc::c_int) -> io::Result<Self> {
         let addr = unsafe {
             libc::mmap(
                 ptr::null_mut(),
                 size,
                 libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
                 -1,
                 0,
             )
         };
 
-        assert!(!addr.is_null() && addr != libc::MAP_FAILED, "mmap failed");
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
 
-        Self {
+        Ok(Self {
             data: addr as _,
             size,
-        }
+        })
     }
 }
 
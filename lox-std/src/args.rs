@@ -0,0 +1,58 @@
+use lox_gc::Gc;
+use lox_vm::memory::List;
+use lox_vm::string::LoxString;
+use lox_vm::value::Value;
+use lox_vm::VmError;
+
+// Unpack the arguments of native functions, failing with `IncorrectArity` for the wrong number of arguments
+// and with `UnexpectedValue` for arguments of the wrong type.
+
+pub(crate) fn number(value: Value) -> Result<f64, VmError> {
+    if value.is_number() {
+        Ok(value.as_number())
+    } else {
+        Err(VmError::UnexpectedValue)
+    }
+}
+
+pub(crate) fn string(value: Value) -> Result<Gc<LoxString>, VmError> {
+    value.try_cast::<LoxString>().ok_or(VmError::UnexpectedValue)
+}
+
+pub(crate) fn one_value(args: &[Value]) -> Result<Value, VmError> {
+    match args {
+        [value] => Ok(*value),
+        _ => Err(VmError::IncorrectArity),
+    }
+}
+
+pub(crate) fn one_number(args: &[Value]) -> Result<f64, VmError> {
+    number(one_value(args)?)
+}
+
+pub(crate) fn two_numbers(args: &[Value]) -> Result<(f64, f64), VmError> {
+    match args {
+        [a, b] => Ok((number(*a)?, number(*b)?)),
+        _ => Err(VmError::IncorrectArity),
+    }
+}
+
+pub(crate) fn numbers(args: &[Value]) -> Result<impl Iterator<Item = f64>, VmError> {
+    let numbers = args.iter().map(|value| number(*value)).collect::<Result<Vec<_>, _>>()?;
+    Ok(numbers.into_iter())
+}
+
+pub(crate) fn one_string(args: &[Value]) -> Result<Gc<LoxString>, VmError> {
+    string(one_value(args)?)
+}
+
+pub(crate) fn two_strings(args: &[Value]) -> Result<(Gc<LoxString>, Gc<LoxString>), VmError> {
+    match args {
+        [a, b] => Ok((string(*a)?, string(*b)?)),
+        _ => Err(VmError::IncorrectArity),
+    }
+}
+
+pub(crate) fn one_list(args: &[Value]) -> Result<Gc<List>, VmError> {
+    one_value(args)?.try_cast::<List>().ok_or(VmError::UnexpectedValue)
+}
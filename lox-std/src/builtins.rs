@@ -0,0 +1,91 @@
+use lox_vm::memory::{BoundMethod, Class, Closure, Import, Instance, List, NativeFunction};
+use lox_vm::string::LoxString;
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::one_value;
+use crate::weak::WeakMap;
+
+/// The globals to convert values and ask what they are: `str`, `num`, `len`, `type` and `is`.
+pub fn set_builtins(native: &mut Native) {
+    // `str(value)` returns the text that `print value;` writes.
+    native.set_global_fn("str", |native, _this, args| {
+        let value = one_value(args)?;
+        if value.is_object_of_type::<LoxString>() {
+            return Ok(value);
+        }
+
+        Ok(native.string(&value.to_string()))
+    });
+
+    // `num(string)` returns the number the string holds, ignoring surrounding whitespace, or nil if it holds none.
+    native.set_global_fn("num", |_native, _this, args| {
+        let value = one_value(args)?;
+        if value.is_number() {
+            return Ok(value);
+        }
+
+        let string = value.try_cast::<LoxString>().ok_or(VmError::UnexpectedValue)?;
+        let number = string.as_str().trim().parse::<f64>().ok().filter(|number| number.is_finite());
+        Ok(number.map_or(Value::NIL, Value::from))
+    });
+
+    // `len(value)` returns the characters of a string, the elements of a list,
+    // the live entries of a WeakMap or the fields of an instance.
+    native.set_global_fn("len", |_native, _this, args| {
+        let value = one_value(args)?;
+        let len = if let Some(string) = value.try_cast::<LoxString>() {
+            string.as_str().chars().count()
+        } else if let Some(list) = value.try_cast::<List>() {
+            list.len()
+        } else if let Some(map) = value.try_cast::<WeakMap>() {
+            map.len()
+        } else if let Some(instance) = value.try_cast::<Instance>() {
+            instance.entries().count()
+        } else {
+            return Err(VmError::UnexpectedValue);
+        };
+
+        Ok((len as f64).into())
+    });
+
+    // `type(value)` returns "nil", "boolean", "number", "string", "function", "class" or "module",
+    // and otherwise the name of the value's class, such as "List" or the class of an instance.
+    native.set_global_fn("type", |native, _this, args| {
+        let value = one_value(args)?;
+        let name = type_name(native, value);
+        Ok(native.string(&name))
+    });
+
+    // `is(value, Class)` is true if `value` is an instance of exactly `Class`.
+    native.set_global_fn("is", |native, _this, args| {
+        let (value, class) = match args {
+            [value, class] => (*value, class.try_cast::<Class>().ok_or(VmError::UnexpectedValue)?),
+            _ => return Err(VmError::IncorrectArity),
+        };
+
+        Ok(native.class_of(value).is_some_and(|of| of == class).into())
+    });
+}
+
+fn type_name(native: &Native, value: Value) -> String {
+    let name = if value.is_nil() {
+        "nil"
+    } else if value.is_bool() {
+        "boolean"
+    } else if value.is_number() {
+        "number"
+    } else if value.is_object_of_type::<LoxString>() || value.is_object_of_type::<String>() {
+        "string"
+    } else if value.is_object_of_type::<Closure>() || value.is_object_of_type::<NativeFunction>() || value.is_object_of_type::<BoundMethod>() {
+        "function"
+    } else if value.is_object_of_type::<Class>() {
+        "class"
+    } else if value.is_object_of_type::<Import>() {
+        "module"
+    } else {
+        return native.class_of(value).map_or_else(|| "object".to_string(), |class| class.name.as_str().to_string());
+    };
+
+    name.to_string()
+}
@@ -0,0 +1,170 @@
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+
+use lox_gc::Trace;
+use lox_vm::memory::List;
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::{one_string, two_strings};
+use crate::io::{io_error, trim_line_ending};
+
+/// A file opened for reading with `fs.open(path)`.
+#[derive(Trace)]
+pub struct File {
+    #[trace(skip)]
+    path: String,
+    /// `None` once the file is closed.
+    #[trace(skip)]
+    reader: RefCell<Option<BufReader<std::fs::File>>>,
+}
+
+impl File {
+    fn read_line(&self, native: &Native) -> Result<Value, VmError> {
+        let mut reader = self.reader.borrow_mut();
+        let reader = reader.as_mut().ok_or_else(|| {
+            VmError::Io(ErrorKind::Other, format!("{}: file is closed", self.path))
+        })?;
+
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(io_error(&self.path))? == 0 {
+            return Ok(Value::NIL);
+        }
+
+        Ok(native.string(trim_line_ending(&line)))
+    }
+}
+
+/// `import "fs";` for files and directories.
+pub fn set_fs(native: &mut Native) {
+    native.register_module("fs", |m| {
+        m.function("readFile", |native, _this, args| {
+            let path = one_string(args)?;
+            let contents = std::fs::read_to_string(path.as_str()).map_err(io_error(path.as_str()))?;
+            Ok(native.string(&contents))
+        });
+
+        m.function("writeFile", |_native, _this, args| {
+            let (path, contents) = two_strings(args)?;
+            std::fs::write(path.as_str(), contents.as_str()).map_err(io_error(path.as_str()))?;
+            Ok(Value::NIL)
+        });
+
+        m.function("appendFile", |_native, _this, args| {
+            let (path, contents) = two_strings(args)?;
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path.as_str())
+                .and_then(|mut file| file.write_all(contents.as_str().as_bytes()))
+                .map_err(io_error(path.as_str()))?;
+            Ok(Value::NIL)
+        });
+
+        m.function("exists", |_native, _this, args| {
+            let path = one_string(args)?;
+            Ok(std::path::Path::new(path.as_str()).exists().into())
+        });
+
+        // `listDir(path)` returns the names of the entries in a directory, sorted.
+        m.function("listDir", |native, _this, args| {
+            let path = one_string(args)?;
+
+            let mut names = std::fs::read_dir(path.as_str())
+                .and_then(|entries| {
+                    entries
+                        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .map_err(io_error(path.as_str()))?;
+            names.sort();
+
+            let list = native.manage(List::new(0));
+            lox_gc::with_root(&list, || {
+                for name in names {
+                    list.push(native.string(&name));
+                }
+            });
+
+            Ok(Value::from_object(list.erase()))
+        });
+
+        // `mkdir(path)` also creates the missing parent directories.
+        m.function("mkdir", |_native, _this, args| {
+            let path = one_string(args)?;
+            std::fs::create_dir_all(path.as_str()).map_err(io_error(path.as_str()))?;
+            Ok(Value::NIL)
+        });
+
+        // `remove(path)` removes a file, or a directory with everything in it.
+        m.function("remove", |_native, _this, args| {
+            let path = one_string(args)?;
+            std::fs::symlink_metadata(path.as_str())
+                .and_then(|metadata| {
+                    if metadata.is_dir() {
+                        std::fs::remove_dir_all(path.as_str())
+                    } else {
+                        std::fs::remove_file(path.as_str())
+                    }
+                })
+                .map_err(io_error(path.as_str()))?;
+
+            Ok(Value::NIL)
+        });
+
+        m.function("open", |native, _this, args| {
+            let path = one_string(args)?;
+            let file = std::fs::File::open(path.as_str()).map_err(io_error(path.as_str()))?;
+
+            let file = native.manage(File {
+                path: path.as_str().to_string(),
+                reader: RefCell::new(Some(BufReader::new(file))),
+            });
+
+            Ok(Value::from_object(file.erase()))
+        });
+    });
+
+    let file_class = native.register_class::<File>("File");
+
+    // `readLine()` returns the next line without its line ending, or nil at the end of the file.
+    native.set_method(file_class, "readLine", |native, this, args| {
+        let file = this.try_cast::<File>().ok_or(VmError::UnexpectedValue)?;
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        file.read_line(native)
+    });
+
+    // `lines()` returns the lines that were not read yet.
+    native.set_method(file_class, "lines", |native, this, args| {
+        let file = this.try_cast::<File>().ok_or(VmError::UnexpectedValue)?;
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        let list = native.manage(List::new(0));
+        lox_gc::with_root(&list, || {
+            loop {
+                let line = file.read_line(native)?;
+                if line.is_nil() {
+                    return Ok(Value::from_object(list.erase()));
+                }
+
+                list.push(line);
+            }
+        })
+    });
+
+    native.set_method(file_class, "close", |_native, this, args| {
+        let file = this.try_cast::<File>().ok_or(VmError::UnexpectedValue)?;
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        file.reader.borrow_mut().take();
+        Ok(Value::NIL)
+    });
+}
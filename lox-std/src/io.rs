@@ -0,0 +1,41 @@
+use std::io::{BufRead, Write};
+
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::one_value;
+
+/// `import "io";` for the standard streams.
+pub fn set_io(native: &mut Native) {
+    native.register_module("io", |m| {
+        // `readLine()` returns the next line of stdin without its line ending, or nil at the end.
+        m.function("readLine", |native, _this, args| {
+            if !args.is_empty() {
+                return Err(VmError::IncorrectArity);
+            }
+
+            let mut line = String::new();
+            if std::io::stdin().lock().read_line(&mut line).map_err(io_error("stdin"))? == 0 {
+                return Ok(Value::NIL);
+            }
+
+            Ok(native.string(trim_line_ending(&line)))
+        });
+
+        m.function("eprint", |_native, _this, args| {
+            let value = one_value(args)?;
+            writeln!(std::io::stderr(), "{value}").map_err(io_error("stderr"))?;
+            Ok(Value::NIL)
+        });
+    });
+}
+
+pub(crate) fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// The error of a native function for an `io::Error` while using `path`, which is a file, stream or program.
+pub(crate) fn io_error(path: &str) -> impl FnOnce(std::io::Error) -> VmError + '_ {
+    move |err| VmError::Io(err.kind(), format!("{path}: {err}"))
+}
@@ -0,0 +1,149 @@
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+
+use lox_gc::Gc;
+use lox_vm::memory::{Class, Instance, List};
+use lox_vm::string::LoxString;
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::one_string;
+
+/// `import "json";` to convert between Lox values and JSON text.
+pub fn set_json(native: &mut Native) {
+    native.register_module("json", |m| {
+        // `parse(text)` turns JSON objects into instances with a field per key, and arrays into lists.
+        m.function("parse", |native, _this, args| {
+            let text = one_string(args)?;
+
+            let json: serde_json::Value = serde_json::from_str(text.as_str())
+                .map_err(|err| parse_error(text.as_str(), &err))?;
+
+            let class = native.manage(Class::new("Object"));
+            Ok(lox_gc::with_root(&class, || from_json(native, &json, class)))
+        });
+
+        // `stringify(value, indent)` writes lists, instances, strings, numbers, bools and nil as JSON,
+        // over multiple lines indented by `indent` spaces if it is given.
+        m.function("stringify", |native, _this, args| {
+            let (value, indent) = match args {
+                [value] => (*value, None),
+                [value, indent] if indent.is_number() => (*value, Some(indent.as_number().max(0.0) as usize)),
+                [_, _] => return Err(VmError::UnexpectedValue),
+                _ => return Err(VmError::IncorrectArity),
+            };
+
+            let json = to_json(native, value, &mut Vec::new())?;
+            let text = match indent {
+                Some(indent) => {
+                    let indent = " ".repeat(indent);
+                    let mut text = Vec::new();
+                    let mut serializer = serde_json::Serializer::with_formatter(&mut text, PrettyFormatter::with_indent(indent.as_bytes()));
+                    json.serialize(&mut serializer).map_err(|_| VmError::UnexpectedValue)?;
+                    String::from_utf8_lossy(&text).into_owned()
+                },
+                None => json.to_string(),
+            };
+
+            Ok(native.string(&text))
+        });
+    });
+}
+
+/// A parse error of `text`, with its position as a byte offset instead of a line and column.
+fn parse_error(text: &str, err: &serde_json::Error) -> VmError {
+    let line_start: usize = text.split_inclusive('\n')
+        .take(err.line().saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let offset = line_start + err.column().saturating_sub(1);
+
+    let message = err.to_string();
+    let reason = message.rsplit_once(" at line ").map_or(message.as_str(), |(reason, _)| reason);
+    VmError::Parse(format!("{reason} at byte {offset}"))
+}
+
+/// Build `json` in the heap. Nothing references the new objects until they are returned, so they are rooted by hand.
+fn from_json(native: &mut Native, json: &serde_json::Value, class: Gc<Class>) -> Value {
+    match json {
+        serde_json::Value::Null => Value::NIL,
+        serde_json::Value::Bool(value) => (*value).into(),
+        serde_json::Value::Number(value) => value.as_f64().unwrap_or(f64::NAN).into(),
+        serde_json::Value::String(value) => native.string(value),
+        serde_json::Value::Array(elements) => {
+            let list = native.manage(List::new(0));
+            lox_gc::with_root(&list, || {
+                for element in elements {
+                    let value = from_json(native, element, class);
+                    list.push(value);
+                }
+            });
+            Value::from_object(list.erase())
+        },
+        serde_json::Value::Object(fields) => {
+            let instance = native.manage(Instance::new(class));
+            lox_gc::with_root(&instance, || {
+                for (name, field) in fields {
+                    let value = from_json(native, field, class);
+                    let symbol = native.intern(name);
+                    instance.set_field(symbol, value);
+                }
+            });
+            Value::from_object(instance.erase())
+        },
+    }
+}
+
+/// Convert `value` to JSON. `path` holds the containers currently being converted, to reject cycles.
+fn to_json(native: &Native, value: Value, path: &mut Vec<Gc<()>>) -> Result<serde_json::Value, VmError> {
+    if value.is_nil() {
+        return Ok(serde_json::Value::Null);
+    } else if value.is_bool() {
+        return Ok((!value.is_falsey()).into());
+    } else if value.is_number() {
+        return number(value.as_number());
+    }
+
+    if let Some(string) = value.try_cast::<LoxString>() {
+        return Ok(string.as_str().into());
+    }
+
+    let object = value.as_object();
+    if path.contains(&object) {
+        return Err(VmError::UnexpectedValue);
+    }
+
+    path.push(object);
+
+    let json = if let Some(list) = value.try_cast::<List>() {
+        let mut elements = Vec::with_capacity(list.len());
+        for index in 0..list.len() {
+            elements.push(to_json(native, list.get(index), path)?);
+        }
+        serde_json::Value::Array(elements)
+    } else if let Some(instance) = value.try_cast::<Instance>() {
+        let mut fields = serde_json::Map::new();
+        for (symbol, value) in instance.entries() {
+            let name = native.resolve(symbol).unwrap_or_default().to_string();
+            fields.insert(name, to_json(native, value, path)?);
+        }
+        serde_json::Value::Object(fields)
+    } else {
+        return Err(VmError::UnexpectedValue);
+    };
+
+    path.pop();
+
+    Ok(json)
+}
+
+/// Whole numbers are written without a fraction, and JSON has no NaN or infinity.
+fn number(value: f64) -> Result<serde_json::Value, VmError> {
+    if value.fract() == 0.0 && value.abs() < 9007199254740992.0 {
+        return Ok((value as i64).into());
+    }
+
+    serde_json::Number::from_f64(value)
+        .map(serde_json::Value::Number)
+        .ok_or(VmError::UnexpectedValue)
+}
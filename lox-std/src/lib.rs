@@ -1,34 +1,96 @@
+mod args;
+mod builtins;
+mod fs;
+mod io;
+mod json;
+mod math;
+mod os;
+mod random;
+mod reflect;
+mod thread;
+mod time;
+mod weak;
+
 use lox_vm::VirtualMachine;
+use lox_vm::VmError;
+use lox_vm::value::Value;
 
 /// Add the lox standard library to a VirtualMachine instance.
-/// Right now the stdlib consists of 'clock'.
+/// Right now the stdlib consists of 'clock', 'gc', 'retainedBy', 'str', 'num', 'len', 'type', 'is',
+/// the reflection functions 'fields', 'hasField', 'getField', 'setField', 'methods', 'staticMethods' and 'classOf', the threading primitives 'spawn' and 'Channel',
+/// the weak reference primitives 'WeakRef', 'WeakMap' and 'onCollect', and the modules "math", "io", "fs", "os", "json", "time" and "random".
 pub fn set_stdlib(vm: &mut VirtualMachine) {
     let mut native = vm.native();
 
-    native.set_global_fn("clock", |_this, _args| {
+    native.set_global_fn("clock", |_native, _this, _args| {
         use std::time::{SystemTime, UNIX_EPOCH};
 
         let time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs_f64();
-        time.into()
+        Ok(time.into())
     });
 
-    native.set_method(native.list_class(), "append", |this, args| {
+    native.set_global_fn("gc", |native, _this, args| {
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        native.collect()?;
+        Ok(Value::NIL)
+    });
+
+    // `retainedBy(object)` lists the objects that keep `object` alive, starting at a root,
+    // or returns nil if no other object references it.
+    native.set_global_fn("retainedBy", |native, _this, args| {
         use lox_vm::memory::List;
 
-        if !this.is_object_of_type::<List>() {
-            //TODO Not panic!?
-            panic!("this is not a list");
-        }
+        let object = args::one_value(args)?;
+        let path = match native.retained_by(object) {
+            Some(path) => path,
+            None => return Ok(Value::NIL),
+        };
+
+        let list = native.manage(List::new(0));
+        lox_gc::with_root(&list, || {
+            for step in path {
+                list.push(native.string(&step));
+            }
+        });
+
+        Ok(Value::from_object(list.erase()))
+    });
+
+    native.set_method(native.list_class(), "append", |_native, this, args| {
+        use lox_vm::memory::List;
+
+        let this_list = match this.try_cast::<List>() {
+            Some(list) => list,
+            None => return Err(VmError::UnexpectedValue),
+        };
 
-        let this_list = this.as_object().cast::<List>();
         for value in args {
             this_list.push(*value);
         }
 
-        this
+        Ok(this)
     });
+
+    builtins::set_builtins(&mut native);
+    reflect::set_reflect(&mut native);
+    thread::set_thread(&mut native);
+    weak::set_weak(&mut native);
+    math::set_math(&mut native);
+    io::set_io(&mut native);
+    fs::set_fs(&mut native);
+    os::set_os(&mut native, Vec::new());
+    json::set_json(&mut native);
+    time::set_time(&mut native);
+    random::set_random(&mut native);
 }
 
+/// Make `args` the arguments of the script, which it reads as `os.args`.
+pub fn set_args(vm: &mut VirtualMachine, args: Vec<String>) {
+    os::set_os(&mut vm.native(), args);
+}
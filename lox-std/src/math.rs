@@ -0,0 +1,83 @@
+use lox_vm::{Native, VmError};
+
+use crate::args::{number, numbers, one_number, two_numbers};
+
+/// Add functions of one number, such as `"sqrt" => f64::sqrt`.
+/// Native functions cannot capture, so each one is a separate closure.
+macro_rules! unary {
+    ($m:ident, $($name:literal => $op:path),* $(,)?) => {
+        $(
+            $m.function($name, |_native, _this, args| Ok($op(one_number(args)?).into()));
+        )*
+    };
+}
+
+/// `import "math";` for functions and constants on numbers.
+pub fn set_math(native: &mut Native) {
+    native.register_module("math", |m| {
+        unary!(m,
+            "abs" => f64::abs,
+            "floor" => f64::floor,
+            "ceil" => f64::ceil,
+            "round" => f64::round,
+            "trunc" => f64::trunc,
+            "sqrt" => f64::sqrt,
+            "exp" => f64::exp,
+            "log" => f64::ln,
+            "log2" => f64::log2,
+            "log10" => f64::log10,
+            "sin" => f64::sin,
+            "cos" => f64::cos,
+            "tan" => f64::tan,
+            "asin" => f64::asin,
+            "acos" => f64::acos,
+            "atan" => f64::atan,
+        );
+
+        m.function("atan2", |_native, _this, args| {
+            let (y, x) = two_numbers(args)?;
+            Ok(y.atan2(x).into())
+        });
+
+        m.function("pow", |_native, _this, args| {
+            let (base, exponent) = two_numbers(args)?;
+            Ok(base.powf(exponent).into())
+        });
+
+        m.function("hypot", |_native, _this, args| {
+            let (x, y) = two_numbers(args)?;
+            Ok(x.hypot(y).into())
+        });
+
+        m.function("min", |_native, _this, args| {
+            Ok(numbers(args)?.reduce(f64::min).ok_or(VmError::IncorrectArity)?.into())
+        });
+
+        m.function("max", |_native, _this, args| {
+            Ok(numbers(args)?.reduce(f64::max).ok_or(VmError::IncorrectArity)?.into())
+        });
+
+        m.function("clamp", |_native, _this, args| {
+            let (value, min, max) = match args {
+                [value, min, max] => (number(*value)?, number(*min)?, number(*max)?),
+                _ => return Err(VmError::IncorrectArity),
+            };
+
+            // Unlike `f64::clamp`, this does not panic when `min` is greater than `max`.
+            Ok(value.max(min).min(max).into())
+        });
+
+        m.function("isNaN", |_native, _this, args| {
+            Ok(one_number(args)?.is_nan().into())
+        });
+
+        m.function("isInfinite", |_native, _this, args| {
+            Ok(one_number(args)?.is_infinite().into())
+        });
+
+        m.constant("pi", std::f64::consts::PI);
+        m.constant("e", std::f64::consts::E);
+        m.constant("inf", f64::INFINITY);
+        m.constant("nan", f64::NAN);
+    });
+}
@@ -0,0 +1,104 @@
+use std::process::Command;
+
+use lox_vm::memory::{Class, Instance, List};
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::{one_string, string, two_strings};
+use crate::io::io_error;
+
+/// `import "os";` for the process and its environment, where `args` are the arguments of the script.
+pub fn set_os(native: &mut Native, args: Vec<String>) {
+    native.register_module("os", move |m| {
+        let list = m.native().manage(List::new(0));
+        lox_gc::with_root(&list, || {
+            for arg in &args {
+                list.push(m.native().string(arg));
+            }
+        });
+        m.constant("args", Value::from_object(list.erase()));
+
+        let platform = m.native().string(std::env::consts::OS);
+        m.constant("platform", platform);
+
+        // `env(name)` returns the value of an environment variable, or nil if it is not set.
+        m.function("env", |native, _this, args| {
+            let name = one_string(args)?;
+
+            match std::env::var_os(name.as_str()) {
+                Some(value) => Ok(native.string(&value.to_string_lossy())),
+                None => Ok(Value::NIL),
+            }
+        });
+
+        m.function("setEnv", |_native, _this, args| {
+            let (name, value) = two_strings(args)?;
+
+            std::env::set_var(name.as_str(), value.as_str());
+            Ok(Value::NIL)
+        });
+
+        // `exit(code)` stops the script, and the CLI exits with `code`, 0 if it is left out.
+        m.function("exit", |_native, _this, args| {
+            let code = match args {
+                [] => 0.0,
+                [code] if code.is_number() => code.as_number(),
+                [_] => return Err(VmError::UnexpectedValue),
+                _ => return Err(VmError::IncorrectArity),
+            };
+
+            Err(VmError::Exit(code as i32))
+        });
+
+        m.function("cwd", |native, _this, args| {
+            if !args.is_empty() {
+                return Err(VmError::IncorrectArity);
+            }
+
+            let cwd = std::env::current_dir().map_err(io_error("current directory"))?;
+            Ok(native.string(&cwd.to_string_lossy()))
+        });
+
+        // `run(command, args)` runs a program and waits for it.
+        // It returns an object with the exit `status`, nil if there is none, and the captured `stdout` and `stderr`.
+        m.function("run", |native, _this, args| {
+            let (command, arguments) = match args {
+                [command] => (string(*command)?, Vec::new()),
+                [command, arguments] => (string(*command)?, strings(*arguments)?),
+                _ => return Err(VmError::IncorrectArity),
+            };
+
+            let output = Command::new(command.as_str())
+                .args(arguments)
+                .output()
+                .map_err(io_error(command.as_str()))?;
+
+            let class = native.manage(Class::new("Output"));
+            let output_object = lox_gc::with_root(&class, || native.manage(Instance::new(class)));
+            lox_gc::with_root(&output_object, || {
+                let status = output.status.code().map_or(Value::NIL, |code| (code as f64).into());
+                let stdout = native.string(&String::from_utf8_lossy(&output.stdout));
+                let stderr = lox_gc::with_root(&stdout, || native.string(&String::from_utf8_lossy(&output.stderr)));
+
+                let fields = [("status", status), ("stdout", stdout), ("stderr", stderr)];
+                // Setting a field can grow the fields, so the values are rooted until they are all set.
+                lox_gc::with_root(&(stdout, stderr), || {
+                    for (field, value) in fields {
+                        let field = native.intern(field);
+                        output_object.set_field(field, value);
+                    }
+                });
+            });
+
+            Ok(Value::from_object(output_object.erase()))
+        });
+    });
+}
+
+/// The strings in a list.
+fn strings(value: Value) -> Result<Vec<String>, VmError> {
+    let list = value.try_cast::<List>().ok_or(VmError::UnexpectedValue)?;
+    (0..list.len())
+        .map(|index| Ok(string(list.get(index))?.as_str().to_string()))
+        .collect()
+}
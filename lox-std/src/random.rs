@@ -0,0 +1,207 @@
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lox_gc::Trace;
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::{number, one_list};
+
+/// The xoshiro256** generator by David Blackman and Sebastiano Vigna, see <https://prng.di.unimi.it/>.
+/// Its state is seeded with splitmix64, so the same seed gives the same numbers on every platform.
+struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    fn new(seed: u64) -> Self {
+        let mut seed = seed;
+        let mut splitmix = || {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+
+        Self {
+            state: [splitmix(), splitmix(), splitmix(), splitmix()],
+        }
+    }
+
+    /// A generator seeded from the clock, for when no seed is given.
+    fn from_clock() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Self::new(nanos as u64 ^ (nanos >> 64) as u64)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *s1 << 17;
+
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+
+        result
+    }
+
+    /// A number in `[0, 1)` from the upper 53 bits.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// A number in `[0, range)`, rejecting the few values that would make low numbers more likely.
+    fn below(&mut self, range: u64) -> u64 {
+        let threshold = range.wrapping_neg() % range;
+        loop {
+            let value = self.next_u64();
+            if value >= threshold {
+                return value % range;
+            }
+        }
+    }
+}
+
+/// The generator state of an object created with `random.Random(seed)`.
+#[derive(Trace)]
+pub struct Random {
+    #[trace(skip)]
+    generator: RefCell<Xoshiro256>,
+}
+
+thread_local! {
+    /// The generator used by the functions of the module itself.
+    static GENERATOR: RefCell<Xoshiro256> = RefCell::new(Xoshiro256::from_clock());
+}
+
+/// Add a module function using the shared generator and a method on `Random` using its own generator,
+/// both named `$name` and implemented by `$op(native, generator, args)`.
+macro_rules! generators {
+    ($native:ident, $class:ident, $($name:literal => $op:ident),* $(,)?) => {
+        $native.register_module("random", |m| {
+            $(
+                m.function($name, |native, _this, args| {
+                    GENERATOR.with(|generator| $op(native, &mut generator.borrow_mut(), args))
+                });
+            )*
+
+            // `Random(seed)` returns an independent generator. The same seed always gives the same sequence.
+            m.function("Random", |native, _this, args| {
+                let generator = match args {
+                    [] => Xoshiro256::from_clock(),
+                    [seed] => Xoshiro256::new(seed_of(*seed)?),
+                    _ => return Err(VmError::IncorrectArity),
+                };
+
+                let random = native.manage(Random {
+                    generator: RefCell::new(generator),
+                });
+                Ok(Value::from_object(random.erase()))
+            });
+        });
+
+        $(
+            $native.set_method($class, $name, |native, this, args| {
+                let random = this.try_cast::<Random>().ok_or(VmError::UnexpectedValue)?;
+                let mut generator = random.generator.borrow_mut();
+                $op(native, &mut generator, args)
+            });
+        )*
+    };
+}
+
+/// `import "random";` for random numbers, either from a shared generator or from `Random(seed)`.
+pub fn set_random(native: &mut Native) {
+    let class = native.register_class::<Random>("Random");
+
+    generators!(native, class,
+        "random" => random,
+        "int" => int,
+        "choice" => choice,
+        "shuffle" => shuffle,
+        "gaussian" => gaussian,
+    );
+}
+
+/// `random()` returns a number from 0 up to but not including 1.
+fn random(_native: &mut Native, generator: &mut Xoshiro256, args: &[Value]) -> Result<Value, VmError> {
+    if !args.is_empty() {
+        return Err(VmError::IncorrectArity);
+    }
+
+    Ok(generator.next_f64().into())
+}
+
+/// `int(lo, hi)` returns a whole number from `lo` up to and including `hi`.
+fn int(_native: &mut Native, generator: &mut Xoshiro256, args: &[Value]) -> Result<Value, VmError> {
+    let (lo, hi) = match args {
+        [lo, hi] => (whole_number(*lo)?, whole_number(*hi)?),
+        _ => return Err(VmError::IncorrectArity),
+    };
+
+    if lo > hi {
+        return Err(VmError::UnexpectedValue);
+    }
+
+    let range = (hi - lo) as u64 + 1;
+    Ok(((lo + generator.below(range) as i64) as f64).into())
+}
+
+/// `choice(list)` returns a random element of a non-empty list.
+fn choice(_native: &mut Native, generator: &mut Xoshiro256, args: &[Value]) -> Result<Value, VmError> {
+    let list = one_list(args)?;
+    if list.is_empty() {
+        return Err(VmError::UnexpectedValue);
+    }
+
+    Ok(list.get(generator.below(list.len() as u64) as usize))
+}
+
+/// `shuffle(list)` puts the elements of the list in a random order, with a Fisher-Yates shuffle.
+fn shuffle(_native: &mut Native, generator: &mut Xoshiro256, args: &[Value]) -> Result<Value, VmError> {
+    let list = one_list(args)?;
+    for index in (1..list.len()).rev() {
+        let other = generator.below(index as u64 + 1) as usize;
+        let value = list.get(index);
+        list.set(index, list.get(other));
+        list.set(other, value);
+    }
+
+    Ok(Value::NIL)
+}
+
+/// `gaussian(mean, deviation)` returns a normally distributed number, by default with mean 0 and standard deviation 1.
+fn gaussian(_native: &mut Native, generator: &mut Xoshiro256, args: &[Value]) -> Result<Value, VmError> {
+    let (mean, deviation) = match args {
+        [] => (0.0, 1.0),
+        [mean, deviation] => (number(*mean)?, number(*deviation)?),
+        _ => return Err(VmError::IncorrectArity),
+    };
+
+    // Box-Muller transform, with the first number in (0, 1] so its logarithm is finite.
+    let radius = (-2.0 * (1.0 - generator.next_f64()).ln()).sqrt();
+    let angle = 2.0 * std::f64::consts::PI * generator.next_f64();
+    Ok((mean + deviation * radius * angle.cos()).into())
+}
+
+/// Whole numbers small enough that every number between them is exact.
+fn whole_number(value: Value) -> Result<i64, VmError> {
+    let number = number(value)?;
+    if number.fract() != 0.0 || number.abs() > (1u64 << 53) as f64 {
+        return Err(VmError::UnexpectedValue);
+    }
+
+    Ok(number as i64)
+}
+
+fn seed_of(value: Value) -> Result<u64, VmError> {
+    Ok(whole_number(value)? as u64)
+}
@@ -0,0 +1,95 @@
+use lox_gc::Gc;
+use lox_vm::interner::Symbol;
+use lox_vm::memory::{Class, Instance, List};
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::{one_value, string};
+
+/// The globals to look into instances and classes by name: `fields`, `hasField`, `getField`, `setField`,
+/// `methods`, `staticMethods` and `classOf`. The name of a class is `Class.name`.
+pub fn set_reflect(native: &mut Native) {
+    // `fields(instance)` lists the names of the fields, sorted.
+    native.set_global_fn("fields", |native, _this, args| {
+        let instance = instance(one_value(args)?)?;
+        Ok(names(native, instance.entries()))
+    });
+
+    native.set_global_fn("hasField", |native, _this, args| {
+        let (instance, name) = instance_and_name(native, args)?;
+        Ok(instance.field(name).is_some().into())
+    });
+
+    // `getField(instance, name)` is `instance.name` for a name known only at runtime. Methods are not fields.
+    native.set_global_fn("getField", |native, _this, args| {
+        let (instance, name) = instance_and_name(native, args)?;
+        instance.field(name).ok_or(VmError::UndefinedProperty)
+    });
+
+    // `setField(instance, name, value)` is `instance.name = value`, and returns the value.
+    native.set_global_fn("setField", |native, _this, args| {
+        let (instance, name, value) = match args {
+            [instance, name, value] => (self::instance(*instance)?, self::name(native, *name)?, *value),
+            _ => return Err(VmError::IncorrectArity),
+        };
+
+        instance.set_field(name, value);
+        Ok(value)
+    });
+
+    // `methods(Class)` lists the names of the methods called on instances, sorted.
+    native.set_global_fn("methods", |native, _this, args| {
+        let class = one_class(args)?;
+        Ok(names(native, class.method_entries()))
+    });
+
+    // `staticMethods(Class)` lists the names of the methods declared with `class`, which are called on the class itself.
+    native.set_global_fn("staticMethods", |native, _this, args| {
+        let class = one_class(args)?;
+        Ok(names(native, class.static_method_entries()))
+    });
+
+    // `classOf(value)` returns the class method calls on the value look in, or nil if it has none.
+    native.set_global_fn("classOf", |native, _this, args| {
+        let value = one_value(args)?;
+        Ok(native.class_of(value).map_or(Value::NIL, |class| Value::from_object(class.erase())))
+    });
+}
+
+/// A new list of the names of `entries` as strings, sorted.
+fn names(native: &mut Native, entries: impl Iterator<Item = (Symbol, Value)>) -> Value {
+    let mut names: Vec<String> = entries
+        .filter_map(|(symbol, _)| native.resolve(symbol).map(str::to_string))
+        .collect();
+    names.sort();
+
+    let list = native.manage(List::new(0));
+    lox_gc::with_root(&list, || {
+        for name in names {
+            list.push(native.string(&name));
+        }
+    });
+
+    Value::from_object(list.erase())
+}
+
+fn instance(value: Value) -> Result<Gc<Instance>, VmError> {
+    value.try_cast::<Instance>().ok_or(VmError::UnexpectedValue)
+}
+
+fn one_class(args: &[Value]) -> Result<Gc<Class>, VmError> {
+    one_value(args)?.try_cast::<Class>().ok_or(VmError::UnexpectedValue)
+}
+
+/// The symbol of a field name given as a string.
+fn name(native: &mut Native, value: Value) -> Result<Symbol, VmError> {
+    let name = string(value)?;
+    Ok(native.intern(name.as_str()))
+}
+
+fn instance_and_name(native: &mut Native, args: &[Value]) -> Result<(Gc<Instance>, Symbol), VmError> {
+    match args {
+        [instance, name] => Ok((self::instance(*instance)?, self::name(native, *name)?)),
+        _ => Err(VmError::IncorrectArity),
+    }
+}
@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use lox_gc::{Gc, Trace};
+use lox_vm::memory::{Class, Instance, List};
+use lox_vm::string::LoxString;
+use lox_vm::value::Value;
+use lox_vm::{Native, VirtualMachine, VmError};
+
+use crate::args::{one_value, string};
+
+/// A value deep-copied out of a VM heap, so it can be moved to a VM on another thread.
+enum Message {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Message>),
+    Instance(String, Vec<(String, Message)>),
+    Channel(Arc<Queue>),
+}
+
+struct Queue {
+    messages: Mutex<VecDeque<Message>>,
+    ready: Condvar,
+}
+
+impl Queue {
+    fn new() -> Self {
+        Self {
+            messages: Mutex::new(VecDeque::new()),
+            ready: Condvar::new(),
+        }
+    }
+
+    fn send(&self, message: Message) {
+        let mut messages = self.messages.lock().unwrap();
+        messages.push_back(message);
+        self.ready.notify_one();
+    }
+
+    fn receive(&self) -> Message {
+        let mut messages = self.messages.lock().unwrap();
+        loop {
+            if let Some(message) = messages.pop_front() {
+                return message;
+            }
+
+            messages = self.ready.wait(messages).unwrap();
+        }
+    }
+}
+
+#[derive(Trace)]
+pub struct Channel {
+    #[trace(skip)]
+    queue: Arc<Queue>,
+}
+
+#[derive(Trace)]
+pub struct Thread {
+    #[trace(skip)]
+    handle: RefCell<Option<JoinHandle<Result<(), VmError>>>>,
+}
+
+pub fn set_thread(native: &mut Native) {
+    native.set_global_fn("spawn", spawn);
+
+    native.set_global_fn("Channel", |native, _this, args| {
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        let channel = native.manage(Channel {
+            queue: Arc::new(Queue::new()),
+        });
+
+        Ok(Value::from_object(channel.erase()))
+    });
+
+    let channel_class = native.register_class::<Channel>("Channel");
+
+    native.set_method(channel_class, "send", |native, this, args| {
+        let channel = this.try_cast::<Channel>().ok_or(VmError::UnexpectedValue)?;
+        let value = one_value(args)?;
+        let message = to_message(native, value, &mut Vec::new())?;
+        channel.queue.send(message);
+
+        Ok(Value::NIL)
+    });
+
+    native.set_method(channel_class, "receive", |native, this, args| {
+        let channel = this.try_cast::<Channel>().ok_or(VmError::UnexpectedValue)?;
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        let message = channel.queue.receive();
+
+        Ok(from_message(native, message, &RefCell::new(HashMap::new())))
+    });
+
+    let thread_class = native.register_class::<Thread>("Thread");
+
+    native.set_method(thread_class, "join", |_native, this, args| {
+        let thread = this.try_cast::<Thread>().ok_or(VmError::UnexpectedValue)?;
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        let handle = match thread.handle.borrow_mut().take() {
+            Some(handle) => handle,
+            None => return Ok(Value::NIL),
+        };
+
+        match handle.join() {
+            Ok(Ok(())) => Ok(Value::NIL),
+            Ok(Err(error)) => Err(error),
+            Err(panic) => Err(VmError::Panic(panic_message(&*panic))),
+        }
+    });
+}
+
+/// `spawn(path, message)` runs the module at `path` in a new VM on a worker thread.
+/// The worker sees a deep copy of `message` as the global `message`.
+fn spawn(native: &mut Native, _this: Value, args: &[Value]) -> Result<Value, VmError> {
+    let (path, message) = match args {
+        [path] => (*path, Value::NIL),
+        [path, message] => (*path, *message),
+        _ => return Err(VmError::IncorrectArity),
+    };
+
+    let path = string(path)?;
+    let id = native.resolve_module(path.as_str())?;
+    let module = native.load_module(path.as_str(), &id)?;
+    let message = to_message(native, message, &mut Vec::new())?;
+
+    let print = native.stdout();
+    let loader = native.loader();
+    let heap_config = native.worker_heap_config();
+
+    let handle = std::thread::spawn(move || {
+        let mut vm = VirtualMachine::with_heap_config(heap_config)
+            .map_err(|err| VmError::Io(err.kind(), format!("could not reserve the heap of {id}: {err}")))?;
+        vm.set_stdout(print);
+        vm.set_loader(loader);
+        crate::set_stdlib(&mut vm);
+
+        let mut native = vm.native();
+        let message = from_message(&mut native, message, &RefCell::new(HashMap::new()));
+        native.set_global("message", message);
+
+        vm.interpret_as(id.as_str(), module)
+    });
+
+    let thread = native.manage(Thread {
+        handle: RefCell::new(Some(handle)),
+    });
+
+    Ok(Value::from_object(thread.erase()))
+}
+
+/// The message `panic!` was given, which is a `&str` or a `String` unless the payload was custom, like the message std prints.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// Copy `value` out of the heap. `path` holds the containers currently being copied, to reject cycles.
+fn to_message(native: &Native, value: Value, path: &mut Vec<Gc<()>>) -> Result<Message, VmError> {
+    if value.is_nil() {
+        return Ok(Message::Nil);
+    } else if value.is_bool() {
+        return Ok(Message::Bool(!value.is_falsey()));
+    } else if value.is_number() {
+        return Ok(Message::Number(value.as_number()));
+    }
+
+    if let Some(string) = value.try_cast::<LoxString>() {
+        return Ok(Message::String(string.as_str().to_string()));
+    } else if let Some(channel) = value.try_cast::<Channel>() {
+        return Ok(Message::Channel(channel.queue.clone()));
+    }
+
+    let object = value.as_object();
+    if path.contains(&object) {
+        return Err(VmError::UnexpectedValue);
+    }
+
+    path.push(object);
+
+    let message = if let Some(list) = value.try_cast::<List>() {
+        let mut elements = Vec::with_capacity(list.len());
+        for index in 0..list.len() {
+            elements.push(to_message(native, list.get(index), path)?);
+        }
+        Message::List(elements)
+    } else if let Some(instance) = value.try_cast::<Instance>() {
+        let mut fields = Vec::new();
+        for (symbol, value) in instance.entries() {
+            let name = native.resolve(symbol).unwrap_or_default().to_string();
+            fields.push((name, to_message(native, value, path)?));
+        }
+        Message::Instance(instance.class.name.as_str().to_string(), fields)
+    } else {
+        return Err(VmError::UnexpectedValue);
+    };
+
+    path.pop();
+
+    Ok(message)
+}
+
+/// Rebuild a copied value in the current heap. Instances get a field-only class per class name.
+/// Nothing references the new objects until they are returned, so they are rooted by hand.
+fn from_message(native: &mut Native, message: Message, classes: &RefCell<HashMap<String, Gc<Class>>>) -> Value {
+    match message {
+        Message::Nil => Value::NIL,
+        Message::Bool(value) => value.into(),
+        Message::Number(value) => value.into(),
+        Message::String(value) => native.string(&value),
+        Message::List(elements) => {
+            let list = lox_gc::with_root(classes, || native.manage(List::new(0)));
+            lox_gc::with_root(&(&list, classes), || {
+                for element in elements {
+                    let value = from_message(native, element, classes);
+                    list.push(value);
+                }
+            });
+            Value::from_object(list.erase())
+        },
+        Message::Instance(class, fields) => {
+            let existing = classes.borrow().get(&class).copied();
+            let class = match existing {
+                Some(class) => class,
+                None => {
+                    let new = lox_gc::with_root(classes, || native.manage(Class::new(class.as_str())));
+                    classes.borrow_mut().insert(class, new);
+                    new
+                },
+            };
+
+            let instance = lox_gc::with_root(classes, || native.manage(Instance::new(class)));
+            lox_gc::with_root(&(&instance, classes), || {
+                for (name, field) in fields {
+                    let value = from_message(native, field, classes);
+                    let symbol = native.intern(&name);
+                    instance.set_field(symbol, value);
+                }
+            });
+            Value::from_object(instance.erase())
+        },
+        Message::Channel(queue) => {
+            let channel = native.manage(Channel {
+                queue,
+            });
+            Value::from_object(channel.erase())
+        },
+    }
+}
@@ -0,0 +1,341 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use lox_gc::{Gc, Trace};
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::{number, one_number, string, two_strings};
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// The largest number of seconds from the UNIX epoch a date can be, in either direction.
+/// It covers the years up to a billion that `date(...)` accepts, and keeps the calendar arithmetic from overflowing.
+const MAX_SECONDS: f64 = 1e9 * 366.0 * SECONDS_PER_DAY;
+
+/// The pattern used by `format()` without arguments.
+const ISO_8601: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// A point in time in UTC, created with `time.now()`, `time.date(...)`, `time.fromTimestamp(seconds)` or `time.parse(text, pattern)`.
+#[derive(Trace)]
+pub struct DateTime {
+    /// Seconds since the UNIX epoch.
+    seconds: f64,
+}
+
+/// The calendar fields of a [`DateTime`].
+struct Civil {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    /// 1 for Monday to 7 for Sunday.
+    weekday: i64,
+}
+
+impl DateTime {
+    fn civil(&self) -> Civil {
+        let days = (self.seconds / SECONDS_PER_DAY).floor();
+        let time = (self.seconds - days * SECONDS_PER_DAY) as i64;
+        let days = days as i64;
+        let (year, month, day) = civil_from_days(days);
+
+        Civil {
+            year,
+            month,
+            day,
+            hour: time / 3600,
+            minute: time / 60 % 60,
+            second: time % 60,
+            weekday: (days + 3).rem_euclid(7) + 1,
+        }
+    }
+
+    fn format(&self, pattern: &str) -> Result<String, VmError> {
+        let civil = self.civil();
+        let mut text = String::new();
+        let mut chars = pattern.char_indices();
+        while let Some((_, c)) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some((_, 'Y')) => text.push_str(&format!("{:04}", civil.year)),
+                Some((_, 'm')) => text.push_str(&format!("{:02}", civil.month)),
+                Some((_, 'd')) => text.push_str(&format!("{:02}", civil.day)),
+                Some((_, 'H')) => text.push_str(&format!("{:02}", civil.hour)),
+                Some((_, 'M')) => text.push_str(&format!("{:02}", civil.minute)),
+                Some((_, 'S')) => text.push_str(&format!("{:02}", civil.second)),
+                Some((_, '%')) => text.push('%'),
+                Some((offset, c)) => return Err(unknown_directive(c, offset)),
+                None => return Err(VmError::Parse(format!("unfinished directive at byte {}", pattern.len()))),
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Read `text` as laid out by `pattern`, the reverse of [`DateTime::format`].
+    /// Fields missing from the pattern default to midnight on 1 January 1970.
+    fn parse(text: &str, pattern: &str) -> Result<DateTime, VmError> {
+        let mut fields = [1970, 1, 1, 0, 0, 0];
+        let mut offset = 0;
+        let mut chars = pattern.char_indices();
+        while let Some((_, c)) = chars.next() {
+            let expected = match c {
+                '%' => match chars.next() {
+                    Some((_, '%')) => '%',
+                    Some((index, c)) => {
+                        let (field, digits, range) = match c {
+                            'Y' => (0, 4, 0..=9999),
+                            'm' => (1, 2, 1..=12),
+                            'd' => (2, 2, 1..=31),
+                            'H' => (3, 2, 0..=23),
+                            'M' => (4, 2, 0..=59),
+                            'S' => (5, 2, 0..=59),
+                            _ => return Err(unknown_directive(c, index)),
+                        };
+
+                        let number = text.get(offset..offset + digits)
+                            .filter(|number| number.bytes().all(|byte| byte.is_ascii_digit()))
+                            .ok_or_else(|| VmError::Parse(format!("expected {digits} digits at byte {offset}")))?;
+                        let value: i64 = number.parse().unwrap_or_default();
+                        if !range.contains(&value) {
+                            return Err(VmError::Parse(format!("{value} is out of range at byte {offset}")));
+                        }
+
+                        fields[field] = value;
+                        offset += digits;
+                        continue;
+                    },
+                    None => return Err(VmError::Parse(format!("unfinished directive at byte {}", pattern.len()))),
+                },
+                c => c,
+            };
+
+            if !text[offset..].starts_with(expected) {
+                return Err(VmError::Parse(format!("expected '{expected}' at byte {offset}")));
+            }
+            offset += expected.len_utf8();
+        }
+
+        if offset < text.len() {
+            return Err(VmError::Parse(format!("unexpected text at byte {offset}")));
+        }
+
+        let [year, month, day, hour, minute, second] = fields;
+        if day > days_in_month(year, month) {
+            return Err(VmError::Parse(format!("{year}-{month:02} has no day {day}")));
+        }
+
+        Ok(DateTime::from_civil(year, month, day, hour, minute, second))
+    }
+
+    fn from_civil(year: i64, month: i64, day: i64, hour: i64, minute: i64, second: i64) -> DateTime {
+        let days = days_from_civil(year, month, day);
+        DateTime {
+            seconds: (days * 86400 + hour * 3600 + minute * 60 + second) as f64,
+        }
+    }
+}
+
+fn unknown_directive(c: char, offset: usize) -> VmError {
+    VmError::Parse(format!("unknown directive '%{c}' at byte {}", offset - 1))
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar, see <http://howardhinnant.github.io/date_algorithms.html>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The year, month and day of `days` since 1970-01-01, the inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Add methods returning a calendar field, such as `"year" => year`.
+macro_rules! fields {
+    ($native:ident, $class:ident, $($name:literal => $field:ident),* $(,)?) => {
+        $(
+            $native.set_method($class, $name, |_native, this, args| {
+                if !args.is_empty() {
+                    return Err(VmError::IncorrectArity);
+                }
+
+                Ok((date_time(this)?.civil().$field as f64).into())
+            });
+        )*
+    };
+}
+
+/// `import "time";` for clocks, dates in UTC and durations, which are numbers of seconds.
+pub fn set_time(native: &mut Native) {
+    native.register_module("time", |m| {
+        m.constant("second", 1.0);
+        m.constant("minute", 60.0);
+        m.constant("hour", 3600.0);
+        m.constant("day", SECONDS_PER_DAY);
+
+        m.function("now", |native, _this, args| {
+            if !args.is_empty() {
+                return Err(VmError::IncorrectArity);
+            }
+
+            let seconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            Ok(new_date_time(native, seconds))
+        });
+
+        // `monotonic()` returns seconds since an arbitrary start, which never go backwards, to measure durations.
+        m.function("monotonic", |_native, _this, args| {
+            static START: OnceLock<Instant> = OnceLock::new();
+
+            if !args.is_empty() {
+                return Err(VmError::IncorrectArity);
+            }
+
+            Ok(START.get_or_init(Instant::now).elapsed().as_secs_f64().into())
+        });
+
+        m.function("sleep", |_native, _this, args| {
+            let seconds = one_number(args)?;
+            let duration = Duration::try_from_secs_f64(seconds).map_err(|_| VmError::UnexpectedValue)?;
+            std::thread::sleep(duration);
+            Ok(Value::NIL)
+        });
+
+        m.function("fromTimestamp", |native, _this, args| {
+            let seconds = checked_seconds(one_number(args)?)?;
+            Ok(new_date_time(native, seconds))
+        });
+
+        // `date(year, month, day)` and optionally the hour, minute and second, in UTC.
+        m.function("date", |native, _this, args| {
+            if !(3..=6).contains(&args.len()) {
+                return Err(VmError::IncorrectArity);
+            }
+
+            let mut fields = [0; 6];
+            for (field, arg) in fields.iter_mut().zip(args) {
+                let number = number(*arg)?;
+                if number.fract() != 0.0 || number.abs() > 1e9 {
+                    return Err(VmError::UnexpectedValue);
+                }
+                *field = number as i64;
+            }
+
+            let [year, month, day, hour, minute, second] = fields;
+            if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day)
+                || !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..60).contains(&second) {
+                return Err(VmError::UnexpectedValue);
+            }
+
+            let date_time = DateTime::from_civil(year, month, day, hour, minute, second);
+            Ok(new_date_time(native, date_time.seconds))
+        });
+
+        // `parse(text, pattern)` reads a date written with the directives of `format(pattern)`.
+        m.function("parse", |native, _this, args| {
+            let (text, pattern) = two_strings(args)?;
+
+            let date_time = DateTime::parse(text.as_str(), pattern.as_str())?;
+            Ok(new_date_time(native, date_time.seconds))
+        });
+    });
+
+    let class = native.register_class::<DateTime>("DateTime");
+
+    fields!(native, class,
+        "year" => year,
+        "month" => month,
+        "day" => day,
+        "hour" => hour,
+        "minute" => minute,
+        "second" => second,
+        "weekday" => weekday,
+    );
+
+    native.set_method(class, "timestamp", |_native, this, args| {
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        Ok(date_time(this)?.seconds.into())
+    });
+
+    // `format(pattern)` writes %Y, %m, %d, %H, %M and %S as the zero-padded fields and %% as '%'.
+    // Without a pattern the date is written as ISO 8601.
+    native.set_method(class, "format", |native, this, args| {
+        let date_time = date_time(this)?;
+        let text = match args {
+            [] => date_time.format(ISO_8601)?,
+            [pattern] => date_time.format(string(*pattern)?.as_str())?,
+            _ => return Err(VmError::IncorrectArity),
+        };
+
+        Ok(native.string(&text))
+    });
+
+    // `add(seconds)` returns a new date, later by a positive and earlier by a negative number of seconds.
+    native.set_method(class, "add", |native, this, args| {
+        let seconds = checked_seconds(date_time(this)?.seconds + one_number(args)?)?;
+        Ok(new_date_time(native, seconds))
+    });
+
+    // `since(other)` returns the seconds from `other` to this date.
+    native.set_method(class, "since", |_native, this, args| {
+        let other = match args {
+            [other] => date_time(*other)?,
+            _ => return Err(VmError::IncorrectArity),
+        };
+
+        Ok((date_time(this)?.seconds - other.seconds).into())
+    });
+}
+
+fn new_date_time(native: &Native, seconds: f64) -> Value {
+    let date_time = native.manage(DateTime { seconds });
+    Value::from_object(date_time.erase())
+}
+
+/// `seconds` if it is a date the calendar supports, which excludes NaN and infinity.
+fn checked_seconds(seconds: f64) -> Result<f64, VmError> {
+    if seconds.abs() <= MAX_SECONDS {
+        Ok(seconds)
+    } else {
+        Err(VmError::UnexpectedValue)
+    }
+}
+
+fn date_time(value: Value) -> Result<Gc<DateTime>, VmError> {
+    value.try_cast::<DateTime>().ok_or(VmError::UnexpectedValue)
+}
@@ -0,0 +1,160 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use lox_gc::{Gc, Trace, Weak};
+use lox_vm::value::Value;
+use lox_vm::{Native, VmError};
+
+use crate::args::one_value;
+
+#[derive(Trace)]
+pub struct WeakRef {
+    target: Weak<()>,
+}
+
+/// A table with weakly held object keys, compared by identity.
+/// Values are held strongly, so a value that references its own key keeps that entry alive.
+#[derive(Trace)]
+pub struct WeakMap {
+    entries: RefCell<HashMap<u64, (Weak<()>, Value)>>,
+    /// Entries with collected keys are dropped once the table grows to this size.
+    prune_at: Cell<usize>,
+}
+
+impl WeakMap {
+    const MIN_PRUNE_AT: usize = 8;
+
+    fn new() -> Self {
+        Self {
+            entries: RefCell::new(HashMap::new()),
+            prune_at: Cell::new(Self::MIN_PRUNE_AT),
+        }
+    }
+
+    fn get(&self, key: Gc<()>) -> Option<Value> {
+        let entries = self.entries.borrow();
+        match entries.get(&key.to_bits()) {
+            // A collected key's address may be reused by a new object.
+            Some((weak, value)) if weak.upgrade() == Some(key) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn set(&self, key: Gc<()>, value: Value) {
+        let weak = lox_gc::downgrade(key);
+        let mut entries = self.entries.borrow_mut();
+
+        if entries.len() >= self.prune_at.get() {
+            entries.retain(|_, (weak, _)| weak.upgrade().is_some());
+            self.prune_at.set((entries.len() * 2).max(Self::MIN_PRUNE_AT));
+        }
+
+        lox_gc::write_barrier(&weak);
+        lox_gc::write_barrier(&value);
+        entries.insert(key.to_bits(), (weak, value));
+    }
+
+    /// The number of entries whose key was not collected.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.borrow().values().filter(|(weak, _)| weak.upgrade().is_some()).count()
+    }
+
+    fn delete(&self, key: Gc<()>) -> bool {
+        let existed = self.get(key).is_some();
+        self.entries.borrow_mut().remove(&key.to_bits());
+        existed
+    }
+}
+
+pub fn set_weak(native: &mut Native) {
+    native.set_global_fn("WeakRef", |native, _this, args| {
+        let target = match args {
+            [target] if target.is_object() => target.as_object(),
+            [_] => return Err(VmError::UnexpectedValue),
+            _ => return Err(VmError::IncorrectArity),
+        };
+
+        let weak = native.manage(WeakRef {
+            target: lox_gc::downgrade(target),
+        });
+
+        Ok(Value::from_object(weak.erase()))
+    });
+
+    let weak_ref_class = native.register_class::<WeakRef>("WeakRef");
+
+    native.set_method(weak_ref_class, "get", |_native, this, args| {
+        let weak = this.try_cast::<WeakRef>().ok_or(VmError::UnexpectedValue)?;
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        match weak.target.upgrade() {
+            Some(target) => Ok(Value::from_object(target)),
+            None => Ok(Value::NIL),
+        }
+    });
+
+    native.set_global_fn("WeakMap", |native, _this, args| {
+        if !args.is_empty() {
+            return Err(VmError::IncorrectArity);
+        }
+
+        let map = native.manage(WeakMap::new());
+
+        Ok(Value::from_object(map.erase()))
+    });
+
+    let weak_map_class = native.register_class::<WeakMap>("WeakMap");
+
+    native.set_method(weak_map_class, "get", |_native, this, args| {
+        let map = this.try_cast::<WeakMap>().ok_or(VmError::UnexpectedValue)?;
+        let key = object_key(one_value(args)?)?;
+        Ok(map.get(key).unwrap_or(Value::NIL))
+    });
+
+    native.set_method(weak_map_class, "has", |_native, this, args| {
+        let map = this.try_cast::<WeakMap>().ok_or(VmError::UnexpectedValue)?;
+        let key = object_key(one_value(args)?)?;
+        Ok(map.get(key).is_some().into())
+    });
+
+    native.set_method(weak_map_class, "set", |_native, this, args| {
+        let map = this.try_cast::<WeakMap>().ok_or(VmError::UnexpectedValue)?;
+        let (key, value) = match args {
+            [key, value] => (object_key(*key)?, *value),
+            _ => return Err(VmError::IncorrectArity),
+        };
+
+        map.set(key, value);
+
+        Ok(this)
+    });
+
+    native.set_method(weak_map_class, "delete", |_native, this, args| {
+        let map = this.try_cast::<WeakMap>().ok_or(VmError::UnexpectedValue)?;
+        let key = object_key(one_value(args)?)?;
+        Ok(map.delete(key).into())
+    });
+
+    // `onCollect(object, callback)` calls `callback` after `object` has been collected.
+    // The callback is kept alive by the VM, so it must not capture `object`.
+    native.set_global_fn("onCollect", |native, _this, args| {
+        let (object, callback) = match args {
+            [object, callback] => (object_key(*object)?, *callback),
+            _ => return Err(VmError::IncorrectArity),
+        };
+
+        native.add_finalizer(object, callback);
+
+        Ok(Value::NIL)
+    });
+}
+
+fn object_key(value: Value) -> Result<Gc<()>, VmError> {
+    if value.is_object() {
+        Ok(value.as_object())
+    } else {
+        Err(VmError::UnexpectedValue)
+    }
+}
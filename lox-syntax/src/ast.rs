@@ -66,6 +66,11 @@ pub enum Stmt {
         Vec<WithSpan<Stmt>>,
     ),
     Import(WithSpan<String>, Option<Vec<WithSpan<String>>>),
+    ImportAs(WithSpan<String>, WithSpan<Identifier>),
+    /// A `var`, `fun` or `class` declaration that other modules can import.
+    Export(Box<WithSpan<Stmt>>),
+    /// A method or `var` field in a class body declared with `class`, which belongs to the class instead of its instances.
+    Static(Box<WithSpan<Stmt>>),
 }
 
 pub type Ast = Vec<WithSpan<Stmt>>;
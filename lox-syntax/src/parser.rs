@@ -40,6 +40,19 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// The kind of the token after the next one.
+    pub fn peek_second(&self) -> TokenKind {
+        match self.tokens.get(self.cursor + 1) {
+            Some(t) => t.into(),
+            None => TokenKind::Eof,
+        }
+    }
+
+    /// Whether the next token is the identifier `name`, for words that are only keywords in some places.
+    pub fn check_identifier(&self, name: &str) -> bool {
+        matches!(&self.peek_token().value, Token::Identifier(identifier) if identifier == name)
+    }
+
     pub fn check(&self, match_token: TokenKind) -> bool {
         let token = self.peek();
         token == match_token
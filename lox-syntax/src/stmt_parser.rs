@@ -19,10 +19,26 @@ fn parse_declaration(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
         TokenKind::Var => parse_var_declaration(it),
         TokenKind::Fun => parse_function_declaration(it),
         TokenKind::Class => parse_class_declaration(it),
+        // `export` is only a keyword in front of a declaration, so it can still name a variable.
+        TokenKind::Identifier if it.check_identifier("export") && is_declaration(it.peek_second()) => {
+            parse_export_declaration(it)
+        },
         _ => parse_statement(it),
     }
 }
 
+fn is_declaration(token: TokenKind) -> bool {
+    matches!(token, TokenKind::Var | TokenKind::Fun | TokenKind::Class)
+}
+
+fn parse_export_declaration(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
+    let begin_span = it.expect(TokenKind::Identifier)?;
+    let declaration = parse_declaration(it)?;
+
+    let span = Span::union(begin_span, &declaration);
+    Ok(WithSpan::new(Stmt::Export(Box::new(declaration)), span))
+}
+
 fn parse_statement(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
     match it.peek() {
         TokenKind::Print => parse_print_statement(it),
@@ -46,13 +62,31 @@ fn parse_class_declaration(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
         None
     };
     it.expect(TokenKind::LeftBrace)?;
-    let mut functions: Vec<WithSpan<Stmt>> = vec![];
+    let mut members: Vec<WithSpan<Stmt>> = vec![];
     while !it.check(TokenKind::RightBrace) {
-        functions.push(parse_function(it)?);
+        members.push(parse_class_member(it)?);
     }
     let end_span = it.expect(TokenKind::RightBrace)?;
 
-    Ok(WithSpan::new(Stmt::Class(name.clone(), superclass, functions), Span::union(begin_span, end_span)))
+    Ok(WithSpan::new(Stmt::Class(name.clone(), superclass, members), Span::union(begin_span, end_span)))
+}
+
+/// A method or a `var` field, either of which can start with `class` to make it static.
+fn parse_class_member(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
+    match it.peek() {
+        TokenKind::Var => parse_var_declaration(it),
+        TokenKind::Class => {
+            let begin_span = it.expect(TokenKind::Class)?;
+            let member = match it.peek() {
+                TokenKind::Var => parse_var_declaration(it)?,
+                _ => parse_function(it)?,
+            };
+
+            let span = Span::union(begin_span, &member);
+            Ok(WithSpan::new(Stmt::Static(Box::new(member)), span))
+        },
+        _ => parse_function(it),
+    }
 }
 
 fn parse_function_declaration(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
@@ -158,6 +192,15 @@ fn parse_for_statement(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
 fn parse_import_statement(it: &mut Parser) -> Result<WithSpan<Stmt>, ()> {
     let begin_span = it.expect(TokenKind::Import)?;
     let name = expect_string(it)?;
+    // `as` is only a keyword here, so it can still name a variable.
+    if it.check_identifier("as") {
+        it.advance();
+        let alias = expect_identifier(it)?;
+        let end_span = it.expect(TokenKind::Semicolon)?;
+
+        return Ok(WithSpan::new(Stmt::ImportAs(name, alias), Span::union(begin_span, end_span)));
+    }
+
     let params = if it.check(TokenKind::For) {
         it.expect(TokenKind::For)?;
         Some(parse_params(it)?)
@@ -432,6 +475,44 @@ mod tests {
                 ])
             ), 0..30),
         ]));
+
+        assert_eq!(parse_str("import \"mymodule\" as m;"), Ok(vec![
+            ws(Stmt::ImportAs(
+                ws("mymodule".into(), 7..17),
+                ws("m".into(), 21..22),
+            ), 0..23),
+        ]));
+    }
+
+    #[test]
+    fn test_export_stmt() {
+        assert_eq!(parse_str("export var a;"), Ok(vec![
+            ws(Stmt::Export(Box::new(
+                ws(Stmt::Var(ws("a".into(), 11..12), None), 7..13),
+            )), 0..13),
+        ]));
+
+        // Without a declaration, `export` is a variable.
+        assert_errs("export print 1;", &["Expected ';' got 'print'"]);
+    }
+
+    #[test]
+    fn test_contextual_keywords() {
+        assert_eq!(parse_str("var as = 1;"), Ok(vec![
+            ws(Stmt::Var(ws("as".into(), 4..6), Some(Box::new(ws(Expr::Number(1.0), 9..10)))), 0..11),
+        ]));
+        assert_eq!(parse_str("var export;"), Ok(vec![
+            ws(Stmt::Var(ws("export".into(), 4..10), None), 0..11),
+        ]));
+        assert!(parse_str("fun f(as) { return as; }").is_ok());
+        assert!(parse_str("export = as;").is_ok());
+
+        assert_eq!(parse_str("import \"m\" as as;"), Ok(vec![
+            ws(Stmt::ImportAs(
+                ws("m".into(), 7..10),
+                ws("as".into(), 14..16),
+            ), 0..17),
+        ]));
     }
 
     #[test]
@@ -518,6 +599,38 @@ mod tests {
         assert_errs("class BostonCream < Doughnut < BakedGood {}", &["Expected '{' got '<'"]);
     }
 
+    #[test]
+    fn test_class_members() {
+        unsafe {
+            assert_eq!(
+                parse_str("class a{var b = 1; class var c; class d(){}}"),
+                Ok(vec![
+                    ws(Stmt::Class(
+                        WithSpan::new_unchecked("a".into(), 6, 7),
+                        None,
+                        vec![
+                            ws(Stmt::Var(
+                                WithSpan::new_unchecked("b".into(), 12, 13),
+                                Some(Box::new(ws(Expr::Number(1.0), 16..17)))
+                            ), 8..18),
+                            ws(Stmt::Static(Box::new(ws(Stmt::Var(
+                                WithSpan::new_unchecked("c".into(), 29, 30),
+                                None
+                            ), 25..31))), 19..31),
+                            ws(Stmt::Static(Box::new(ws(Stmt::Function(
+                                WithSpan::new_unchecked("d".into(), 38, 39),
+                                vec![],
+                                vec![]
+                            ), 38..43))), 32..43),
+                        ]
+                    ), 0..44),
+                ])
+            );
+        }
+        assert_errs("class a{print 1;}", &["Expected identifier got 'print'"]);
+        assert_errs("class a{class print}", &["Expected identifier got 'print'"]);
+    }
+
     #[test]
     fn test_for() {
         fn block(what: Vec<WithSpan<Stmt>>, r: Range<u32>) -> WithSpan<Stmt> {
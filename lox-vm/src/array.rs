@@ -52,10 +52,27 @@ impl<T> Array<T> where T: Clone {
     }
 }
 
+/// Keeps a buffer alive while allocating, without tracing its elements.
+/// The owner of the buffer might not be reachable yet.
+struct BufferRoot(*const u8);
+
+unsafe impl Trace for BufferRoot {
+    fn trace(&self, tracer: &mut Tracer) {
+        unsafe {
+            tracer.mark(self.0);
+        }
+    }
+}
+
 impl<T> Clone for Array<T> {
     fn clone(&self) -> Self {
         unsafe {
-            let ptr = lox_gc::alloc(std::alloc::Layout::array::<T>(self.cap).unwrap()) as *mut T;
+            let layout = std::alloc::Layout::array::<T>(self.cap).unwrap();
+            let ptr = if self.cap == 0 {
+                lox_gc::alloc(layout)
+            } else {
+                lox_gc::with_root(&BufferRoot(self.ptr.as_ptr() as *const u8), || lox_gc::alloc(layout))
+            } as *mut T;
             std::ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr, self.cap);
 
             Self {
@@ -94,6 +111,10 @@ impl<T> Array<T> {
         }
     }
 
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
     pub fn push(&mut self, value: T) {
         if self.len == self.cap { self.grow() }
 
@@ -157,7 +178,7 @@ impl<T> Array<T> {
             //TODO We copy more than we need to.
             let old_layout = Layout::array::<T>(self.cap).unwrap();
             let old_ptr = self.ptr.as_ptr() as *mut u8;
-            let new_ptr = unsafe { lox_gc::alloc(new_layout) };
+            let new_ptr = lox_gc::with_root(&BufferRoot(old_ptr), || unsafe { lox_gc::alloc(new_layout) });
 
             unsafe {
                 ptr::copy_nonoverlapping(old_ptr, new_ptr, old_layout.size());
@@ -1,6 +1,6 @@
 use crate::memory::*;
 use crate::value::Value;
-use lox_gc::{Trace, Gc, Tracer};
+use lox_gc::{Trace, Gc};
 use std::cell::Cell;
 use crate::stack::{Stack, StackBlock};
 use crate::VmError;
@@ -8,20 +8,15 @@ use crate::runtime::Signal;
 use arrayvec::ArrayVec;
 use crate::array::Array;
 
+#[derive(Trace)]
 pub struct CallFrame {
     pub base_counter: usize,
     pub closure: Gc<Closure>,
 
+    #[trace(skip)]
     ip: Cell<*const u8>,
 }
 
-unsafe impl Trace for CallFrame {
-    #[inline]
-    fn trace(&self, tracer: &mut Tracer) {
-        self.closure.trace(tracer);
-    }
-}
-
 impl CallFrame {
     pub fn new(object: Gc<Closure>, base_counter: usize) -> Self {
         let ip = object.function.import.chunk(object.function.chunk_index).as_ptr();
@@ -43,23 +38,16 @@ impl CallFrame {
     }
 }
 
+#[derive(Trace)]
 pub struct Fiber {
     pub stack: Stack,
     frames: ArrayVec<CallFrame, 256>,
     stack_block: StackBlock,
     upvalues: Array<Gc<Cell<Upvalue>>>,
+    #[trace(skip)]
     error: Option<VmError>,
 }
 
-unsafe impl Trace for Fiber {
-    fn trace(&self, tracer: &mut Tracer) {
-        self.frames.trace(tracer);
-        self.stack_block.trace(tracer);
-        self.stack.trace(tracer);
-        self.upvalues.trace(tracer);
-    }
-}
-
 impl Fiber {
     pub fn new() -> Self {
         let block = StackBlock::new(2048);
@@ -74,8 +62,8 @@ impl Fiber {
     }
 
     #[cold]
-    pub fn error(&self) -> Option<VmError> {
-        self.error
+    pub fn take_error(&mut self) -> Option<VmError> {
+        self.error.take()
     }
 
     #[cold]
@@ -86,6 +74,14 @@ impl Fiber {
         Signal::RuntimeError
     }
 
+    /// Drop the frames and values a failed run left behind, so that a new top level can run.
+    pub fn unwind(&mut self) {
+        self.close_upvalues(0);
+        self.stack.truncate(0);
+        self.frames.clear();
+        self.error = None;
+    }
+
     pub fn begin_frame(&mut self, closure: Gc<Closure>) {
         let base_counter = self.stack.len() - closure.function.arity - 1;
 
@@ -100,6 +96,11 @@ impl Fiber {
         }
     }
 
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
     #[inline]
     pub fn has_current_frame(&self) -> bool {
         self.frames.len() > 0
@@ -117,14 +118,36 @@ impl Fiber {
         self.current_frame().closure.function.import
     }
 
+    /// Whether the top level of `import` is still running, so it has not finished loading.
+    pub fn is_loading(&self, import: Gc<Import>) -> bool {
+        self.loading().any(|loading| Gc::ptr_eq(loading, import))
+    }
+
+    /// The modules whose top level is still running, from the innermost import outwards.
+    pub fn loading(&self) -> impl Iterator<Item = Gc<Import>> + '_ {
+        self.loading_from(0)
+    }
+
+    /// Like [`Fiber::loading`], but only the frames from `depth` on.
+    pub fn loading_from(&self, depth: usize) -> impl Iterator<Item = Gc<Import>> + '_ {
+        self.frames.iter()
+            .skip(depth)
+            .rev()
+            // The top level of a module is always its first chunk.
+            .filter(|frame| frame.closure.function.chunk_index == 0)
+            .map(|frame| frame.closure.function.import)
+    }
+
     pub fn push_upvalue(&mut self, upvalue: Gc<Cell<Upvalue>>) {
-        self.upvalues.push(upvalue);
+        // `upvalue` is usually new and not reachable from anywhere else yet.
+        lox_gc::with_root(&upvalue, || self.upvalues.push(upvalue));
     }
 
     pub fn close_upvalues(&mut self, index: usize) {
         for upvalue in self.upvalues.iter() {
             if let Some(index) = upvalue.get().is_open_with_range(index) {
                 let value = self.stack.get(index);
+                lox_gc::write_barrier(&value);
                 upvalue.set(Upvalue::Closed(value));
             }
         }
@@ -161,7 +184,10 @@ impl Fiber {
 
     pub fn set_upvalue(&mut self, upvalue: Gc<Cell<Upvalue>>, new_value: Value) {
         match upvalue.get() {
-            Upvalue::Closed(_) => upvalue.set(Upvalue::Closed(new_value)),
+            Upvalue::Closed(_) => {
+                lox_gc::write_barrier(&new_value);
+                upvalue.set(Upvalue::Closed(new_value));
+            },
             Upvalue::Open(index) => self.stack.set(index, new_value),
         }
     }
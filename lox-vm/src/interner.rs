@@ -1,6 +1,7 @@
 use std::collections::HashMap;
+use lox_gc::Trace;
 
-#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
+#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone, Trace)]
 pub struct Symbol(pub u32);
 
 impl Symbol {
@@ -12,6 +13,7 @@ impl Symbol {
 pub struct Interner {
     next: u32,
     map: HashMap<String, Symbol>,
+    names: Vec<String>,
 }
 
 impl Interner {
@@ -19,6 +21,7 @@ impl Interner {
         Self {
             next: 1,
             map: HashMap::default(),
+            names: vec![String::new()],
         }
     }
 
@@ -29,7 +32,17 @@ impl Interner {
             let symbol = Symbol(self.next);
             self.next += 1;
             self.map.insert(string.to_string(), symbol);
+            self.names.push(string.to_string());
             symbol
         }
     }
+
+    /// Returns the string a [`Symbol`] was interned from.
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        if symbol == Symbol::invalid() {
+            return None;
+        }
+
+        self.names.get(symbol.0 as usize).map(|name| name.as_str())
+    }
 }
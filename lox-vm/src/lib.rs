@@ -1,50 +1,168 @@
 pub mod memory;
 pub mod interner;
 pub mod value;
+pub mod string;
 
 mod runtime;
 mod stack;
 mod ops;
 mod fiber;
 mod table;
+mod snapshot;
+mod loader;
+mod native_module;
 
 //TODO Move to lox-gc
 mod array;
-mod string;
+
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use lox_bytecode::bytecode::Module;
+use lox_compiler::Diagnostic;
 use runtime::Runtime;
 use interner::Symbol;
-use memory::{Import, NativeFunction, Class};
+use memory::{Import, NativeFunction, NativeCode, Class};
 use value::Value;
+use string::LoxString;
 use lox_gc::{Gc, Trace};
 
 pub use runtime::VmError;
+pub use snapshot::{HeapSnapshot, KindStats};
+pub use loader::{compile_module, ArchiveLoader, CircularImport, DirectoryLoader, ImportError, ImportSite, LoadError, MemoryLoader, ModuleId, ModuleLoadError, ModuleLoader, ModuleSource};
+pub use lox_gc::{Collector, HeapConfig, Root};
+pub use native_module::NativeModule;
 
 pub struct VirtualMachine {
-    runtime: Runtime,
+    // Boxed so its address can be registered as a root.
+    runtime: Box<Runtime>,
+}
+
+impl Drop for VirtualMachine {
+    fn drop(&mut self) {
+        lox_gc::remove_root(&*self.runtime as *const Runtime);
+    }
 }
 
 impl VirtualMachine {
-    pub fn new() -> Self {
-        Self {
-            runtime: Runtime::new(),
+    /// Constructs a VM, reserving the current thread's heap with the default [`HeapConfig`]
+    /// if that has not happened yet.
+    pub fn new() -> io::Result<Self> {
+        Self::with_heap_config(HeapConfig::default())
+    }
+
+    /// Constructs a VM, reserving the current thread's heap with `config`.
+    /// The heap is per thread, so `config` is ignored if the heap already exists.
+    pub fn with_heap_config(config: HeapConfig) -> io::Result<Self> {
+        lox_gc::init(config)?;
+
+        // Nothing roots the runtime while it is being constructed.
+        let stress = lox_gc::set_stress(false);
+        let mut runtime = Box::new(Runtime::new());
+        runtime.worker_heap_config = config;
+        lox_gc::set_stress(stress);
+
+        unsafe {
+            lox_gc::add_root(&*runtime as *const Runtime);
         }
+
+        Ok(Self {
+            runtime,
+        })
+    }
+
+    /// Constructs a VM whose heap uses `collector`.
+    /// The heap is per thread, so this applies to every VM on the current thread.
+    pub fn with_collector(collector: Collector) -> io::Result<Self> {
+        let vm = Self::new()?;
+        lox_gc::set_collector(collector);
+        Ok(vm)
+    }
+
+    /// Sets the heap size that starts the first collection.
+    pub fn set_gc_threshold(&mut self, bytes: usize) {
+        lox_gc::set_threshold(bytes);
+    }
+
+    /// Sets how far the heap may grow past the live bytes before the next collection.
+    pub fn set_gc_growth_factor(&mut self, factor: f32) {
+        lox_gc::set_growth_factor(factor);
+    }
+
+    /// Count the reachable objects per kind and per Lox class.
+    /// The heap is per thread, so this includes the objects of every VM on the current thread.
+    pub fn heap_snapshot(&self) -> HeapSnapshot {
+        // The runtime is a registered root.
+        HeapSnapshot::new(&lox_gc::snapshot(&[]))
+    }
+
+    /// Describe the chain of objects that keeps `value` alive, from the first object reachable
+    /// from a root down to `value` itself. References from the stack and other roots to `value`
+    /// are ignored, so this is `None` unless `value` is an object that another object references.
+    pub fn retained_by(&self, value: Value) -> Option<Vec<String>> {
+        snapshot::retained_by(value)
+    }
+
+    /// Keep `value` alive until the returned root is dropped,
+    /// for example to call a script's callback after [`VirtualMachine::interpret`] returns.
+    pub fn root<T: 'static + Trace>(&self, value: T) -> Root<T> {
+        Root::new(value)
     }
 
     pub fn set_stdout(&mut self, print: for<'r> fn(&'r str)) {
         self.runtime.print = print;
     }
 
-    pub fn set_import(&mut self, import: for<'r> fn(&'r str) -> Option<Module>) {
-        self.runtime.import = import;
+    /// Sets the heap of the VMs that native functions start on other threads, such as `thread.spawn`.
+    /// By default they use the [`HeapConfig`] this VM was constructed with.
+    pub fn set_worker_heap_config(&mut self, config: HeapConfig) {
+        self.runtime.worker_heap_config = config;
+    }
+
+    /// Sets where imported modules come from. By default there are none.
+    pub fn set_loader(&mut self, loader: impl ModuleLoader + 'static) {
+        self.runtime.loader = Arc::new(loader);
+    }
+
+    /// Compile `source`, the module `id`, checking that the names it imports are exported.
+    /// Imports of modules registered with [`VirtualMachine::register_module`] are left for the VM to check,
+    /// as are the ones the loader cannot load.
+    pub fn compile_module(&self, id: &ModuleId, source: &str) -> Result<Module, Vec<Diagnostic>> {
+        self.runtime.compile_source(id, source)
     }
 
     pub fn interpret(&mut self, module: Module) -> Result<(), VmError> {
-        self.runtime.with_module(module);
+        self.interpret_as("_root", module)
+    }
+
+    /// Like [`VirtualMachine::interpret`], but the module is named `name`,
+    /// so the imports in it are resolved relative to `name`. A run that failed before is discarded first.
+    pub fn interpret_as(&mut self, name: &str, module: Module) -> Result<(), VmError> {
+        self.runtime.with_module(name, module);
         self.runtime.interpret()
     }
 
+    /// Register a module written in Rust, which `import "name";` finds before any module of the loader.
+    /// `build` fills it in the first time a script imports it.
+    pub fn register_module(&mut self, name: &str, build: impl Fn(&mut NativeModule) + 'static) {
+        self.native().register_module(name, build);
+    }
+
+    /// Compile the module `id` again and run its new top level, which replaces the globals it defines.
+    /// Classes keep their identity and get the new methods, so existing instances use them,
+    /// while closures created before keep running the old code.
+    /// A run that failed before is discarded first.
+    /// If the new top level fails, the module keeps its old version and globals.
+    pub fn reload_module(&mut self, id: &ModuleId) -> Result<(), VmError> {
+        self.runtime.reload_module(id)
+    }
+
+    /// The modules loaded so far, including the ones run with [`VirtualMachine::interpret_as`].
+    pub fn modules(&self) -> Vec<ModuleId> {
+        self.runtime.imports.keys().map(|name| ModuleId::new(name.as_str())).collect()
+    }
+
     pub fn native(&mut self) -> Native {
         Native {
             runtime: &mut self.runtime,
@@ -61,33 +179,70 @@ impl Native<'_> {
         self.runtime.interner.intern(value)
     }
 
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.runtime.interner.resolve(symbol)
+    }
+
     pub fn manage<T: 'static + Trace>(&self, value: T) -> Gc<T> {
         lox_gc::manage(value)
     }
 
-    pub fn build_fn(&self, identifier: &str, code: fn(Value, &[Value]) -> Value) -> Gc<NativeFunction> {
+    /// See [`VirtualMachine::root`].
+    pub fn root<T: 'static + Trace>(&self, value: T) -> Root<T> {
+        Root::new(value)
+    }
+
+    /// Collect garbage now, regardless of the threshold,
+    /// and run the finalizer callbacks of everything collected.
+    pub fn collect(&mut self) -> Result<(), VmError> {
+        self.runtime.collect();
+        self.runtime.run_finalizers()
+    }
+
+    /// Call `callback` with no arguments once `object` has been collected.
+    /// The callback is a root, so it must not capture `object`.
+    pub fn add_finalizer(&mut self, object: Gc<()>, callback: Value) {
+        self.runtime.add_finalizer(object, callback);
+    }
+
+    /// See [`VirtualMachine::retained_by`].
+    pub fn retained_by(&self, value: Value) -> Option<Vec<String>> {
+        snapshot::retained_by(value)
+    }
+
+    pub fn string(&self, value: &str) -> Value {
+        let string: Gc<LoxString> = lox_gc::manage(value.into());
+        Value::from_object(string.erase())
+    }
+
+    pub fn build_fn(&self, identifier: &str, code: NativeCode) -> Gc<NativeFunction> {
         lox_gc::manage((NativeFunction {
             name: identifier.into(),
             code,
         }).into())
     }
 
-    pub fn set_fn(&mut self, import: Gc<Import>, identifier: &str, code: fn(Value, &[Value]) -> Value) {
+    pub fn set_fn(&mut self, import: Gc<Import>, identifier: &str, code: NativeCode) {
         let root = self.build_fn(identifier, code);
         let identifier = self.runtime.interner.intern(identifier);
         import.set_global(identifier, Value::from_object(root.erase()))
     }
 
-    pub fn set_method(&mut self, class: Gc<Class>, identifier: &str, code: fn(Value, &[Value]) -> Value) {
+    pub fn set_method(&mut self, class: Gc<Class>, identifier: &str, code: NativeCode) {
         let root = self.build_fn(identifier, code);
         let identifier = self.runtime.interner.intern(identifier);
         class.set_method(identifier, Value::from_object(root.erase()));
     }
 
-    pub fn set_global_fn(&mut self, identifier: &str, code: fn(Value, &[Value]) -> Value) {
+    pub fn set_global_fn(&mut self, identifier: &str, code: NativeCode) {
         self.set_fn(self.global_import(), identifier, code)
     }
 
+    pub fn set_global(&mut self, identifier: &str, value: Value) {
+        let identifier = self.runtime.interner.intern(identifier);
+        self.global_import().set_global(identifier, value);
+    }
+
     pub fn global_import(&self) -> Gc<Import> {
         self.runtime.globals_import()
     }
@@ -100,7 +255,54 @@ impl Native<'_> {
         self.runtime.builtins.string_class
     }
 
+    /// The class that method calls on `value` look in: the class of an instance, `List`,
+    /// or the class registered for a native object. `None` for values without methods.
+    pub fn class_of(&self, value: Value) -> Option<Gc<Class>> {
+        if !value.is_object() {
+            return None;
+        }
+
+        let builtins = &self.runtime.builtins;
+        let class = builtins.class_for_object(value.as_object());
+        (class != builtins.empty_class).then_some(class)
+    }
+
+    /// Register the class used to look up methods on managed objects of type `T`.
+    pub fn register_class<T: 'static>(&mut self, name: &str) -> Gc<Class> {
+        let class = lox_gc::manage(Class::new(name));
+        self.runtime.builtins.native_classes.insert(std::any::TypeId::of::<T>(), class);
+        class
+    }
+
     pub fn add_import(&mut self, import: Gc<Import>) {
-        self.runtime.imports.insert(import.name.clone(), import);
+        self.runtime.add_import(import);
+    }
+
+    /// See [`VirtualMachine::register_module`].
+    pub fn register_module(&mut self, name: &str, build: impl Fn(&mut NativeModule) + 'static) {
+        self.runtime.native_modules.insert(name.to_string(), Rc::new(build));
+    }
+
+    /// Resolve `path` relative to the running module, through the loader configured with [`VirtualMachine::set_loader`].
+    pub fn resolve_module(&self, path: &str) -> Result<ModuleId, VmError> {
+        self.runtime.resolve_import(path)
+    }
+
+    /// Load and compile the module `id`, which `path` resolved to, without running it.
+    pub fn load_module(&self, path: &str, id: &ModuleId) -> Result<Module, VmError> {
+        self.runtime.load_module(path, id)
+    }
+
+    pub fn stdout(&self) -> for<'r> fn(&'r str) {
+        self.runtime.print
+    }
+
+    pub fn loader(&self) -> Arc<dyn ModuleLoader> {
+        self.runtime.loader.clone()
+    }
+
+    /// See [`VirtualMachine::set_worker_heap_config`].
+    pub fn worker_heap_config(&self) -> HeapConfig {
+        self.runtime.worker_heap_config
     }
 }
@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use lox_bytecode::bytecode::Module;
+use lox_compiler::{Diagnostic, Exports, LineOffsets};
+
+/// The module an import refers to, such as a canonical file path.
+/// Imports that resolve to the same id share one module.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModuleId(String);
+
+impl ModuleId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ModuleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What a [`ModuleLoader`] loads, either source code for the VM to compile or an already compiled module.
+pub enum ModuleSource {
+    Source(String),
+    Module(Module),
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// No module matches the import.
+    NotFound,
+    Io(io::Error),
+    /// The module exists, but could not be used, such as source that does not compile.
+    Invalid(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NotFound => write!(f, "module not found"),
+            LoadError::Io(err) => write!(f, "{err}"),
+            LoadError::Invalid(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for LoadError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoadError::NotFound, LoadError::NotFound) => true,
+            // `io::Error` cannot be compared, so errors of the same kind and message are equal.
+            (LoadError::Io(a), LoadError::Io(b)) => a.kind() == b.kind() && a.to_string() == b.to_string(),
+            (LoadError::Invalid(a), LoadError::Invalid(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+/// An `import "path";` statement, by the module it is in and the path as written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportSite {
+    pub importer: ModuleId,
+    pub path: String,
+}
+
+impl fmt::Display for ImportSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\" imported from {}", self.path, self.importer)
+    }
+}
+
+/// A module that could not be found or read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleLoadError {
+    /// The module that could not be read, or `None` if the import did not resolve to a module.
+    pub module: Option<ModuleId>,
+    /// The import that asked for the module, or `None` if the host did, such as for a reload.
+    pub site: Option<ImportSite>,
+    pub error: Arc<LoadError>,
+}
+
+impl fmt::Display for ModuleLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.module, &self.site) {
+            (Some(module), Some(site)) => write!(f, "could not load {module}, {site}")?,
+            (Some(module), None) => write!(f, "could not load {module}")?,
+            (None, Some(site)) => write!(f, "could not import {site}")?,
+            (None, None) => write!(f, "could not load a module")?,
+        }
+
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl std::error::Error for ModuleLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+/// An import of a module that is still loading, because it imports itself through other modules.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircularImport {
+    /// The modules being loaded, from the importer back to the module it imports again.
+    pub cycle: Vec<ModuleId>,
+    pub site: ImportSite,
+}
+
+impl fmt::Display for CircularImport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "circular import, {}:", self.site)?;
+
+        // Printed in the order the modules import each other.
+        let mut cycle = self.cycle.iter().rev();
+        if let Some(first) = cycle.next() {
+            write!(f, " {first}")?;
+            for module in cycle.chain(std::iter::once(first)) {
+                write!(f, " -> {module}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for CircularImport {}
+
+/// A module that was found, but does not compile.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportError {
+    pub module: ModuleId,
+    /// The import that loaded the module, or `None` if the host did, such as for a reload.
+    pub site: Option<ImportSite>,
+    /// The source of `module`, which the spans of `diagnostics` point into.
+    pub source: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ImportError {
+    /// The line in `source` of each diagnostic.
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &Diagnostic)> {
+        let offsets = LineOffsets::new(&self.source);
+        self.diagnostics.iter().map(move |diagnostic| (offsets.line(diagnostic.span.start), diagnostic))
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} does not compile", self.module)?;
+        if let Some(site) = &self.site {
+            write!(f, ", {site}")?;
+        }
+
+        for (line, diagnostic) in self.lines() {
+            write!(f, "\n  {} at line {} in {}", diagnostic.message, line, self.module)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Finds and loads the modules a script imports.
+/// Loaders are shared with the VMs of worker threads, so they must be `Send` and `Sync`.
+pub trait ModuleLoader: Send + Sync {
+    /// The module that `import "spec";` in the module `from` refers to.
+    fn resolve(&self, from: &ModuleId, spec: &str) -> Result<ModuleId, LoadError>;
+
+    fn load(&self, id: &ModuleId) -> Result<ModuleSource, LoadError>;
+}
+
+impl<T: ModuleLoader + ?Sized> ModuleLoader for Arc<T> {
+    fn resolve(&self, from: &ModuleId, spec: &str) -> Result<ModuleId, LoadError> {
+        (**self).resolve(from, spec)
+    }
+
+    fn load(&self, id: &ModuleId) -> Result<ModuleSource, LoadError> {
+        (**self).load(id)
+    }
+}
+
+/// Compile the `source` of the module `id`, checking that the names it imports are exported by the modules that
+/// `loader` finds for them. Imports that `loader` cannot load are left for the VM to report when they run.
+/// Use [`VirtualMachine::compile_module`](crate::VirtualMachine::compile_module) if the VM has native modules.
+pub fn compile_module(loader: &dyn ModuleLoader, id: &ModuleId, source: &str) -> Result<Module, Vec<Diagnostic>> {
+    compile_with_natives(loader, |_| false, id, source)
+}
+
+/// Like [`compile_module`], but the imports for which `is_native` is true are left unchecked,
+/// since native modules are found before the modules of `loader` and build their globals when they run.
+pub(crate) fn compile_with_natives(
+    loader: &dyn ModuleLoader,
+    is_native: impl Fn(&str) -> bool,
+    id: &ModuleId,
+    source: &str,
+) -> Result<Module, Vec<Diagnostic>> {
+    lox_compiler::compile_with_exports(source, |path| {
+        if is_native(path) {
+            return None;
+        }
+
+        let import = loader.resolve(id, path).ok()?;
+        match loader.load(&import).ok()? {
+            // Only parsed, so a circular import cannot recurse.
+            ModuleSource::Source(source) => lox_compiler::exports(&source).ok(),
+            ModuleSource::Module(module) => Some(Exports::of(&module)),
+        }
+    })
+}
+
+/// Loads `<spec>.lox` files next to the importing file, or else from the first search path that has it.
+/// Ids are canonical paths, so every spelling of a path to the same file is the same module.
+#[derive(Clone, Debug, Default)]
+pub struct DirectoryLoader {
+    search_paths: Vec<PathBuf>,
+}
+
+impl DirectoryLoader {
+    pub fn new(search_paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self {
+            search_paths: search_paths.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn search_paths(&self) -> &[PathBuf] {
+        &self.search_paths
+    }
+}
+
+impl ModuleLoader for DirectoryLoader {
+    fn resolve(&self, from: &ModuleId, spec: &str) -> Result<ModuleId, LoadError> {
+        let file = format!("{spec}.lox");
+        let base = Path::new(from.as_str()).parent().unwrap_or(Path::new(""));
+
+        let path = std::iter::once(base)
+            .chain(self.search_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(&file))
+            .find(|candidate| candidate.is_file())
+            .ok_or(LoadError::NotFound)?;
+
+        Ok(ModuleId::new(path.canonicalize()?.to_string_lossy()))
+    }
+
+    fn load(&self, id: &ModuleId) -> Result<ModuleSource, LoadError> {
+        Ok(ModuleSource::Source(std::fs::read_to_string(id.as_str())?))
+    }
+}
+
+/// Modules kept in memory by name, such as `"util/strings"`.
+/// An import is first looked up relative to the name of the importing module, then as is.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryLoader {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.modules.insert(name.into(), source.into());
+    }
+
+    pub fn with(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        self.insert(name, source);
+        self
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn resolve(&self, from: &ModuleId, spec: &str) -> Result<ModuleId, LoadError> {
+        resolve_name(from, spec, |name| self.modules.contains_key(name))
+    }
+
+    fn load(&self, id: &ModuleId) -> Result<ModuleSource, LoadError> {
+        let source = self.modules.get(id.as_str()).ok_or(LoadError::NotFound)?;
+        Ok(ModuleSource::Source(source.clone()))
+    }
+}
+
+/// Modules bundled into a single file, named like those of a [`MemoryLoader`].
+/// Create the file with [`ArchiveLoader::bundle`].
+pub struct ArchiveLoader {
+    data: Vec<u8>,
+    modules: HashMap<String, Range<usize>>,
+}
+
+impl ArchiveLoader {
+    const MAGIC: &'static [u8; 8] = b"LOXARCH1";
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Self> {
+        let mut reader = ArchiveReader {
+            data: &data,
+            offset: 0,
+        };
+
+        if reader.take(Self::MAGIC.len())? != Self::MAGIC {
+            return Err(invalid_archive("not a module archive"));
+        }
+
+        let count = reader.u32()?;
+        let mut modules = HashMap::new();
+        for _ in 0..count {
+            let length = reader.u32()? as usize;
+            let name = std::str::from_utf8(reader.take(length)?)
+                .map_err(|_| invalid_archive("module name is not UTF-8"))?
+                .to_string();
+
+            let length = reader.u32()? as usize;
+            let start = reader.offset;
+            std::str::from_utf8(reader.take(length)?)
+                .map_err(|_| invalid_archive("module source is not UTF-8"))?;
+
+            modules.insert(name, start..reader.offset);
+        }
+
+        Ok(Self {
+            data,
+            modules,
+        })
+    }
+
+    /// An archive of `(name, source)` pairs, for [`ArchiveLoader::from_bytes`].
+    pub fn bundle<'a>(modules: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<u8> {
+        let modules: Vec<_> = modules.into_iter().collect();
+
+        let mut data = Self::MAGIC.to_vec();
+        data.extend((modules.len() as u32).to_le_bytes());
+        for (name, source) in modules {
+            for part in [name, source] {
+                data.extend((part.len() as u32).to_le_bytes());
+                data.extend(part.as_bytes());
+            }
+        }
+
+        data
+    }
+}
+
+impl ModuleLoader for ArchiveLoader {
+    fn resolve(&self, from: &ModuleId, spec: &str) -> Result<ModuleId, LoadError> {
+        resolve_name(from, spec, |name| self.modules.contains_key(name))
+    }
+
+    fn load(&self, id: &ModuleId) -> Result<ModuleSource, LoadError> {
+        let range = self.modules.get(id.as_str()).ok_or(LoadError::NotFound)?;
+        // Sources were checked to be UTF-8 when the archive was opened.
+        let source = String::from_utf8_lossy(&self.data[range.clone()]);
+        Ok(ModuleSource::Source(source.into_owned()))
+    }
+}
+
+struct ArchiveReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ArchiveReader<'a> {
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.offset.checked_add(length)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or_else(|| invalid_archive("archive is truncated"))?;
+
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+fn invalid_archive(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Resolve `spec` to the name of a module that `exists`, relative to `from` first.
+fn resolve_name(from: &ModuleId, spec: &str, exists: impl Fn(&str) -> bool) -> Result<ModuleId, LoadError> {
+    [join_name(from.as_str(), spec), join_name("", spec)]
+        .into_iter()
+        .find(|name| exists(name))
+        .map(ModuleId::new)
+        .ok_or(LoadError::NotFound)
+}
+
+/// `spec` relative to the directory of the module named `from`, without `.` and `..` segments.
+fn join_name(from: &str, spec: &str) -> String {
+    let mut segments: Vec<_> = from.split('/').filter(|segment| !segment.is_empty()).collect();
+    segments.pop();
+
+    for segment in spec.split('/') {
+        match segment {
+            "" | "." => {},
+            ".." => {
+                segments.pop();
+            },
+            segment => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
@@ -1,16 +1,8 @@
 use crate::value::Value;
-use lox_gc::{Gc, Trace, Tracer};
+use lox_gc::{Gc, Trace};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Trace)]
 pub struct BoundMethod {
     pub receiver: Gc<()>,
     pub method: Value,
 }
-
-unsafe impl Trace for BoundMethod {
-    #[inline]
-    fn trace(&self, tracer: &mut Tracer) {
-        self.receiver.trace(tracer);
-        self.method.trace(tracer);
-    }
-}
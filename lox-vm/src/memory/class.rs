@@ -1,21 +1,36 @@
-use std::cell::UnsafeCell;
+use std::cell::{Cell, UnsafeCell};
 use crate::interner::Symbol;
+use crate::memory::Closure;
 use crate::table::Table;
 use crate::value::Value;
-use lox_gc::{Trace, Tracer};
+use lox_gc::{Gc, Trace};
 use crate::string::LoxString;
 
-#[derive(Debug)]
+#[derive(Trace)]
 pub struct Class {
     pub name: LoxString,
     methods: UnsafeCell<Table>,
+    /// Sets the declared fields of each new instance, before `init` runs.
+    initializer: Cell<Option<Gc<Closure>>>,
+    /// Methods declared with `class`, called on the class itself.
+    static_methods: UnsafeCell<Table>,
+    /// Fields declared with `class var`, read and assigned through the class itself.
+    static_fields: UnsafeCell<Table>,
 }
 
 impl Class {
     pub fn new(name: impl Into<LoxString>) -> Self {
+        let name = name.into();
+        let methods = lox_gc::with_root(&name, Table::new);
+        let static_methods = lox_gc::with_root(&(&name, &methods), Table::new);
+        let static_fields = lox_gc::with_root(&(&name, (&methods, &static_methods)), Table::new);
+
         Self {
-            name: name.into(),
-            methods: Default::default(),
+            name,
+            methods: UnsafeCell::new(methods),
+            initializer: Cell::new(None),
+            static_methods: UnsafeCell::new(static_methods),
+            static_fields: UnsafeCell::new(static_fields),
         }
     }
 
@@ -30,17 +45,81 @@ impl Class {
         methods.set(symbol, closure);
     }
 
+    pub fn method_entries(&self) -> impl Iterator<Item = (Symbol, Value)> + '_ {
+        self.methods().iter()
+    }
+
+    pub fn initializer(&self) -> Option<Gc<Closure>> {
+        self.initializer.get()
+    }
+
+    pub fn set_initializer(&self, closure: Gc<Closure>) {
+        lox_gc::write_barrier(&closure);
+        self.initializer.set(Some(closure));
+    }
+
+    pub fn static_method(&self, symbol: Symbol) -> Option<Value> {
+        self.static_methods().get(symbol)
+    }
+
+    pub fn set_static_method(&self, symbol: Symbol, closure: Value) {
+        let static_methods = unsafe { &mut *self.static_methods.get() };
+        static_methods.set(symbol, closure);
+    }
+
+    pub fn static_method_entries(&self) -> impl Iterator<Item = (Symbol, Value)> + '_ {
+        self.static_methods().iter()
+    }
+
+    pub fn static_field(&self, symbol: Symbol) -> Option<Value> {
+        self.static_fields().get(symbol)
+    }
+
+    /// Declare the static field `symbol`, or assign it once declared.
+    pub fn set_static_field(&self, symbol: Symbol, value: Value) {
+        let static_fields = unsafe { &mut *self.static_fields.get() };
+        static_fields.set(symbol, value);
+    }
+
+    /// Replace all methods with those of `other`, such as a newer version of the same class.
+    /// Static fields keep their values, and the ones only `other` declares are added.
+    pub fn replace_methods(&self, other: &Class) {
+        let methods = unsafe { &mut *self.methods.get() };
+        methods.clear();
+        other.methods().copy_to(methods);
+
+        if let Some(initializer) = other.initializer() {
+            self.set_initializer(initializer);
+        } else {
+            self.initializer.set(None);
+        }
+
+        let static_methods = unsafe { &mut *self.static_methods.get() };
+        static_methods.clear();
+        other.static_methods().copy_to(static_methods);
+
+        for (symbol, value) in other.static_fields().iter() {
+            if self.static_field(symbol).is_none() {
+                self.set_static_field(symbol, value);
+            }
+        }
+    }
+
     fn methods(&self) -> &Table {
         unsafe {
             &*self.methods.get()
         }
     }
-}
 
-unsafe impl Trace for Class {
-    #[inline]
-    fn trace(&self, tracer: &mut Tracer) {
-        self.name.trace(tracer);
-        self.methods.trace(tracer);
+    fn static_methods(&self) -> &Table {
+        unsafe {
+            &*self.static_methods.get()
+        }
+    }
+
+    fn static_fields(&self) -> &Table {
+        unsafe {
+            &*self.static_fields.get()
+        }
     }
 }
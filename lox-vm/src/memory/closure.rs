@@ -1,27 +1,22 @@
 use lox_bytecode::bytecode;
 use std::cell::Cell;
-use lox_gc::{Gc, Trace, Tracer};
+use lox_gc::{Gc, Trace};
 use crate::memory::{Import, Upvalue};
 use crate::fiber::Fiber;
 use crate::string::LoxString;
 use crate::array::Array;
 
+#[derive(Trace)]
 pub struct Closure {
     pub function: Function,
     pub upvalues: Array<Gc<Cell<Upvalue>>>,
 }
 
-unsafe impl Trace for Closure {
-    fn trace(&self, tracer: &mut Tracer) {
-        self.upvalues.trace(tracer);
-        self.function.name.trace(tracer);
-        self.function.import.trace(tracer);
-    }
-}
-
 //TODO Drop this entirely and merge this into Closure
+#[derive(Trace)]
 pub struct Function {
     pub name: LoxString,
+    #[trace(skip)]
     pub chunk_index: bytecode::ChunkIndex,
     pub import: Gc<Import>,
     pub arity: usize,
@@ -46,7 +41,6 @@ impl Function {
     }
 }
 
-
 impl Closure {
     pub(crate) fn with_import(import: Gc<Import>) -> Self {
         let function = Function {
@@ -69,7 +63,8 @@ impl Closure {
 
         let base = fiber.current_frame().base_counter;
 
-        let upvalues = closure
+        // New upvalues are rooted by the fiber, but the array is not rooted while they are allocated.
+        let upvalues: Vec<_> = closure
             .upvalues
             .iter()
             .map(|u| {
@@ -92,8 +87,11 @@ impl Closure {
             })
         .collect();
 
+        let upvalues: Array<_> = upvalues.into_iter().collect();
+        let function = lox_gc::with_root(&upvalues, || Function::new(&closure.function, import));
+
         Self {
-            function: Function::new(&closure.function, import),
+            function,
             upvalues,
         }
     }
@@ -1,5 +1,5 @@
 use lox_bytecode::bytecode::{Chunk, ConstantIndex, Module, ClosureIndex, ClassIndex};
-use lox_gc::{Trace, Gc, Tracer};
+use lox_gc::{Trace, Gc};
 use std::cell::UnsafeCell;
 use crate::interner::{Symbol, Interner};
 use lox_bytecode::bytecode;
@@ -7,63 +7,107 @@ use crate::table::Table;
 use crate::value::Value;
 use crate::array::Array;
 use crate::string::LoxString;
+use crate::memory::Class;
+
+/// The globals of a module, kept apart so that every version of a reloaded module shares them.
+#[derive(Trace)]
+pub(crate) struct Globals(UnsafeCell<Table>);
 
 //TODO Drop module 
+#[derive(Trace)]
 pub struct Import {
     pub name: LoxString,
+    #[trace(skip)]
     module: Module,
-    globals: UnsafeCell<Table>,
+    globals: Gc<Globals>,
     symbols: Array<Symbol>,
     strings: Array<Gc<LoxString>>,
-}
-
-unsafe impl Trace for Import {
-    #[inline]
-    fn trace(&self, tracer: &mut Tracer) {
-        self.name.trace(tracer);
-        self.globals.trace(tracer);
-        self.symbols.mark(tracer);
-        self.strings.trace(tracer);
-    }
+    /// The globals other modules may import, or `None` if they all may.
+    #[trace(skip)]
+    exports: Option<Vec<Symbol>>,
 }
 
 impl Import {
     pub fn new(name: impl Into<LoxString>) -> Self {
+        let name = name.into();
+        let globals = lox_gc::with_root(&name, || lox_gc::manage(Globals(UnsafeCell::new(Table::new()))));
+
         Self {
-            name: name.into(),
+            name,
             module: Module::new(),
-            globals: Default::default(),
+            globals,
             symbols: Default::default(),
             strings: Default::default(),
+            exports: None,
         }
     }
 
     pub(crate) fn with_module(name: impl Into<LoxString>, module: Module, interner: &mut Interner) -> Self {
-        let symbols = module.identifiers().iter().map(|identifier| {
-            interner.intern(identifier)
-        }).collect();
+        Self::new(name).fill(module, interner)
+    }
 
-        let strings: Array<Gc<LoxString>> = module.strings.iter().map(|value| {
-            lox_gc::manage(value.into())
-        }).collect();
+    /// A new version of this module running `module`, with the same globals.
+    /// Closures of the old version keep running the old code.
+    pub(crate) fn reloaded(&self, module: Module, interner: &mut Interner) -> Self {
+        let import = Self {
+            name: self.name.clone(),
+            module: Module::new(),
+            globals: self.globals,
+            symbols: Default::default(),
+            strings: Default::default(),
+            exports: None,
+        };
 
-        Self {
-            name: name.into(),
-            module,
-            globals: Default::default(),
-            symbols,
-            strings,
+        import.fill(module, interner)
+    }
+
+    fn fill(self, module: Module, interner: &mut Interner) -> Self {
+        // Nothing references this import yet, so it is rooted by hand while it is filled in.
+        let mut import = self;
+
+        import.symbols = lox_gc::with_root(&import, || {
+            module.identifiers().iter().map(|identifier| {
+                interner.intern(identifier)
+            }).collect()
+        });
+
+        let mut strings = Vec::with_capacity(module.strings.len());
+        for value in module.strings.iter() {
+            let string = lox_gc::with_root(&(&import, &strings), || lox_gc::manage(value.into()));
+            strings.push(string);
         }
+
+        import.strings = lox_gc::with_root(&(&import, &strings), || strings.iter().copied().collect());
+        import.exports = module.exports().map(|exports| {
+            exports.iter().map(|index| import.symbol(*index)).collect()
+        });
+        import.module = module;
+
+        import
     }
 
     pub fn copy_to(&self, other: &Import) {
-        let dst = unsafe { &mut *other.globals.get() };
+        let dst = unsafe { &mut *other.globals.0.get() };
         self.globals().copy_to(dst);
     }
 
     fn globals(&self) -> &Table {
         unsafe {
-            &*self.globals.get()
+            &*self.globals.0.get()
+        }
+    }
+
+    /// A copy of the globals, to put back with [`Import::restore_globals`].
+    pub(crate) fn global_entries(&self) -> Vec<(Symbol, Value)> {
+        self.globals().iter().collect()
+    }
+
+    /// Replace the globals with `entries`, which came from [`Import::global_entries`].
+    pub(crate) fn restore_globals(&self, entries: &[(Symbol, Value)]) {
+        let globals = unsafe { &mut *self.globals.0.get() };
+        globals.clear();
+        for &(key, value) in entries {
+            globals.set(key, value);
         }
     }
 
@@ -101,10 +145,14 @@ impl Import {
     }
 
     pub fn set_global(&self, key: Symbol, value: Value) {
-        let globals = unsafe { &mut *self.globals.get() };
+        let globals = unsafe { &mut *self.globals.0.get() };
         globals.set(key, value);
     }
 
+    pub fn is_exported(&self, key: Symbol) -> bool {
+        self.exports.as_ref().is_none_or(|exports| exports.contains(&key))
+    }
+
     pub fn has_global(&self, key: Symbol) -> bool {
         self.globals().has(key)
     }
@@ -1,10 +1,11 @@
-use lox_gc::{Trace, Gc, Tracer};
+use lox_gc::{Trace, Gc};
 use crate::memory::Class;
 use crate::interner::Symbol;
 use crate::value::Value;
 use crate::table::Table;
 use std::cell::UnsafeCell;
 
+#[derive(Trace)]
 pub struct Instance {
     pub class: Gc<Class>,
     fields: UnsafeCell<Table>,
@@ -28,17 +29,13 @@ impl Instance {
         fields.set(symbol, value);
     }
 
+    pub fn entries(&self) -> impl Iterator<Item = (Symbol, Value)> + '_ {
+        self.fields().iter()
+    }
+
     fn fields(&self) -> &Table {
         unsafe {
             &*self.fields.get()
         }
     }
 }
-
-unsafe impl Trace for Instance {
-    #[inline]
-    fn trace(&self, tracer: &mut Tracer) {
-        self.class.trace(tracer);
-        self.fields.trace(tracer);
-    }
-}
@@ -1,10 +1,11 @@
 use std::fmt::Display;
 use crate::value::Value;
 use std::cell::UnsafeCell;
-use lox_gc::{Trace, Tracer};
+use lox_gc::Trace;
 use crate::stack::Stack;
 use crate::array::Array;
 
+#[derive(Trace)]
 pub struct List {
     data: UnsafeCell<Array<Value>>,
 }
@@ -32,11 +33,28 @@ impl List {
     }
 
     pub fn set(&self, index: usize, value: Value) {
+        lox_gc::write_barrier(&value);
         self.data_mut()[index] = value;
     }
 
     pub fn push(&self, value: Value) {
-        self.data_mut().push(value);
+        lox_gc::write_barrier(&value);
+
+        let data = self.data_mut();
+        if data.len() == data.capacity() {
+            // `value` might not be reachable from anywhere else yet.
+            lox_gc::with_root(&value, || data.push(value));
+        } else {
+            data.push(value);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data().is_empty()
     }
 
     pub fn is_valid(&self, index: usize) -> bool {
@@ -56,12 +74,6 @@ impl List {
     }
 }
 
-unsafe impl Trace for List {
-    fn trace(&self, tracer: &mut Tracer) {
-        self.data.trace(tracer);
-    }
-}
-
 impl Display for List {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "[")?;
@@ -74,4 +86,3 @@ impl Display for List {
         write!(f, "]")
     }
 }
-
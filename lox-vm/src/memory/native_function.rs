@@ -1,10 +1,15 @@
 use crate::value::Value;
-use lox_gc::{Trace, Tracer};
+use lox_gc::Trace;
 use crate::string::LoxString;
+use crate::{Native, VmError};
 
+pub type NativeCode = fn(&mut Native, Value, &[Value]) -> Result<Value, VmError>;
+
+#[derive(Trace)]
 pub struct NativeFunction {
     pub name: LoxString,
-    pub code: fn(Value, &[Value]) -> Value,
+    #[trace(skip)]
+    pub code: NativeCode,
 }
 
 impl std::fmt::Debug for NativeFunction {
@@ -12,9 +17,3 @@ impl std::fmt::Debug for NativeFunction {
         write!(f, "<native function {}>", self.name)
     }
 }
-
-unsafe impl Trace for NativeFunction {
-    fn trace(&self, tracer: &mut Tracer) {
-        self.name.trace(tracer);
-    }
-}
@@ -1,7 +1,7 @@
 use crate::value::Value;
-use lox_gc::{Trace, Tracer};
+use lox_gc::Trace;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Trace)]
 pub enum Upvalue {
     Open(usize),
     Closed(Value),
@@ -37,13 +37,3 @@ impl Upvalue {
         }
     }
 }
-
-unsafe impl Trace for Upvalue {
-    #[inline]
-    fn trace(&self, tracer: &mut Tracer) {
-        match self {
-            Upvalue::Closed(value) => value.trace(tracer),
-            Upvalue::Open(_) => (),
-        }
-    }
-}
@@ -0,0 +1,46 @@
+use std::rc::Rc;
+
+use lox_gc::Gc;
+
+use crate::memory::{Import, NativeCode};
+use crate::value::Value;
+use crate::Native;
+
+/// Fills in a [`NativeModule`] the first time a script imports it.
+pub(crate) type NativeModuleBuilder = Rc<dyn Fn(&mut NativeModule)>;
+
+/// A module written in Rust, registered with [`VirtualMachine::register_module`](crate::VirtualMachine::register_module).
+pub struct NativeModule<'a, 'b> {
+    native: &'a mut Native<'b>,
+    import: Gc<Import>,
+}
+
+impl<'a, 'b> NativeModule<'a, 'b> {
+    pub(crate) fn new(native: &'a mut Native<'b>, import: Gc<Import>) -> Self {
+        Self {
+            native,
+            import,
+        }
+    }
+
+    pub fn function(&mut self, identifier: &str, code: NativeCode) -> &mut Self {
+        self.native.set_fn(self.import, identifier, code);
+        self
+    }
+
+    pub fn constant(&mut self, identifier: &str, value: impl Into<Value>) -> &mut Self {
+        let identifier = self.native.intern(identifier);
+        self.import.set_global(identifier, value.into());
+        self
+    }
+
+    /// The module itself, for example to add classes to it.
+    pub fn import(&self) -> Gc<Import> {
+        self.import
+    }
+
+    /// For building values, such as strings, while the module is filled in.
+    pub fn native(&mut self) -> &mut Native<'b> {
+        self.native
+    }
+}
@@ -64,6 +64,9 @@ impl Runtime {
                 opcode::NUMBER        => self.op_number(),
                 opcode::STRING        => self.op_string(),
                 opcode::RETURN_TOP    => self.op_return_top(),
+                opcode::FIELDS        => self.op_fields(),
+                opcode::STATIC_METHOD => self.op_static_method(),
+                opcode::STATIC_FIELD  => self.op_static_field(),
                 _ => unreachable!(),
             };
 
@@ -71,7 +74,8 @@ impl Runtime {
                 Signal::Done => return Ok(()),
                 Signal::More => (),
                 Signal::RuntimeError => {
-                    return Err(self.fiber.error().unwrap_or(VmError::Unknown));
+                    self.discard_failed_imports();
+                    return Err(self.fiber.take_error().unwrap_or(VmError::Unknown));
                 },
             }
         }
@@ -143,14 +147,30 @@ impl Runtime {
 
         let current_import = self.fiber.current_import();
         let path = current_import.string(index);
+        let name = match self.resolve_import(path.as_str()) {
+            Ok(name) => name,
+            Err(err) => return self.fiber.runtime_error(err),
+        };
+
+        if let Some(import) = self.import(name.as_str()) {
+            if self.fiber.is_loading(import) {
+                let err = self.circular_import(path.as_str(), import);
+                return self.fiber.runtime_error(err);
+            }
 
-        if let Some(import) = self.import(path.as_str()) {
             self.fiber.stack.push(Value::from_object(import.erase()));
 
             return Signal::More;
         }
 
-        let import = match self.load_import(path.as_str()) {
+        // Native modules have no top level to run.
+        if let Some(import) = self.load_native_module(&name) {
+            self.fiber.stack.push(Value::from_object(import.erase()));
+
+            return Signal::More;
+        }
+
+        let import = match self.load_import(path.as_str(), &name) {
             Ok(import) => import,
             Err(err) => return self.fiber.runtime_error(err),
         };
@@ -174,6 +194,10 @@ impl Runtime {
 
         let import = as_obj!(self, import, Import);
 
+        if !import.is_exported(identifier) {
+            return self.fiber.runtime_error(VmError::NotExported);
+        }
+
         let value = import.global(identifier).unwrap_or(Value::NIL);
         self.fiber.stack.push(value);
 
@@ -245,6 +269,54 @@ impl Runtime {
         Signal::More
     }
 
+    pub fn op_fields(&mut self) -> Signal {
+        let (class, closure) = (self.fiber.stack.peek_n(1), self.fiber.stack.peek_n(0));
+
+        let class = as_obj!(self, class, Class);
+        let closure = as_obj!(self, closure, Closure);
+
+        class.set_initializer(closure);
+
+        self.fiber.stack.pop();
+
+        Signal::More
+    }
+
+    pub fn op_static_method(&mut self) -> Signal {
+        let index = self.next_u32() as _;
+
+        let current_import = self.fiber.current_import();
+        let identifier = current_import.symbol(index);
+
+        let (class, closure) = (self.fiber.stack.peek_n(1), self.fiber.stack.peek_n(0));
+
+        let class = as_obj!(self, class, Class);
+        let closure = as_obj!(self, closure, Closure);
+
+        class.set_static_method(identifier, Value::from_object(closure.erase()));
+
+        self.fiber.stack.pop();
+
+        Signal::More
+    }
+
+    pub fn op_static_field(&mut self) -> Signal {
+        let index = self.next_u32() as _;
+
+        let current_import = self.fiber.current_import();
+        let identifier = current_import.symbol(index);
+
+        let (class, value) = (self.fiber.stack.peek_n(1), self.fiber.stack.peek_n(0));
+
+        let class = as_obj!(self, class, Class);
+
+        class.set_static_field(identifier, value);
+
+        self.fiber.stack.pop();
+
+        Signal::More
+    }
+
     pub fn op_return_top(&mut self) -> Signal {
         let base_counter = self.fiber.current_frame().base_counter;
         self.fiber.close_upvalues(base_counter);
@@ -254,7 +326,8 @@ impl Runtime {
             return error;
         }
 
-        if self.fiber.has_current_frame() {
+        // The field initializer of a class called from native code also ends with `RETURN_TOP`.
+        if self.fiber.has_current_frame() && self.fiber.frame_count() != self.exit_depth {
             self.load_ip();
             Signal::More
         } else {
@@ -273,6 +346,11 @@ impl Runtime {
             return error;
         }
 
+        if self.fiber.frame_count() == self.exit_depth {
+            self.fiber.stack.push(result);
+            return Signal::Done;
+        }
+
         self.load_ip();
         self.fiber.stack.push(result);
         Signal::More
@@ -285,6 +363,10 @@ impl Runtime {
     }
 
     pub fn op_call(&mut self) -> Signal {
+        if let Err(error) = self.run_finalizers() {
+            return self.fiber.runtime_error(error);
+        }
+
         let arity = self.next_u8() as _;
 
         let callee = self.fiber.stack.peek_n(arity);
@@ -532,9 +614,17 @@ impl Runtime {
 
         let instance = self.fiber.stack.peek_n(1);
 
-        let instance = as_obj!(self, instance, Instance);
+        if let Some(class) = instance.try_cast::<Class>() {
+            if class.static_field(property).is_none() {
+                return self.fiber.runtime_error(VmError::UndefinedProperty);
+            }
+
+            class.set_static_field(property, self.fiber.stack.peek_n(0));
+        } else {
+            let instance = as_obj!(self, instance, Instance);
 
-        instance.set_field(property, self.fiber.stack.peek_n(0));
+            instance.set_field(property, self.fiber.stack.peek_n(0));
+        }
 
         let value = self.fiber.stack.pop();
         self.fiber.stack.pop();
@@ -563,9 +653,33 @@ impl Runtime {
             }
         }
 
-        let class = self.builtins.class_for_object(instance.as_object());
+        if let Some(import) = instance.try_cast::<Import>() {
+            return match import.global(property).filter(|_| import.is_exported(property)) {
+                Some(value) => {
+                    self.fiber.stack.push(value);
+                    Signal::More
+                },
+                None => self.fiber.runtime_error(VmError::UndefinedProperty),
+            };
+        }
+
+        let method = if let Some(class) = instance.try_cast::<Class>() {
+            if let Some(value) = class.static_field(property) {
+                self.fiber.stack.push(value);
+                return Signal::More;
+            }
+
+            if property == self.name_symbol && class.static_method(property).is_none() {
+                self.push_string(class.name.as_str());
+                return Signal::More;
+            }
 
-        let method = match class.method(property) {
+            class.static_method(property)
+        } else {
+            self.builtins.class_for_object(instance.as_object()).method(property)
+        };
+
+        let method = match method {
             Some(method) => method,
             None => return self.fiber.runtime_error(VmError::UndefinedProperty),
         };
@@ -593,6 +707,10 @@ impl Runtime {
     }
 
     pub fn op_invoke(&mut self) -> Signal {
+        if let Err(error) = self.run_finalizers() {
+            return self.fiber.runtime_error(error);
+        }
+
         let arity = self.next_u8() as _;
         let index = self.next_u32() as _;
 
@@ -612,9 +730,28 @@ impl Runtime {
             }
         }
 
-        let class = self.builtins.class_for_object(instance.as_object());
+        if let Some(import) = instance.try_cast::<Import>() {
+            return match import.global(property).filter(|_| import.is_exported(property)) {
+                Some(value) => {
+                    self.fiber.stack.rset(arity, value);
+                    self.call(arity, value)
+                },
+                None => self.fiber.runtime_error(VmError::UndefinedProperty),
+            };
+        }
+
+        let method = if let Some(class) = instance.try_cast::<Class>() {
+            if let Some(value) = class.static_field(property) {
+                self.fiber.stack.rset(arity, value);
+                return self.call(arity, value);
+            }
+
+            class.static_method(property)
+        } else {
+            self.builtins.class_for_object(instance.as_object()).method(property)
+        };
 
-        let method = match class.method(property) {
+        let method = match method {
             Some(value) => value,
             None => return self.fiber.runtime_error(VmError::UndefinedProperty),
         };
@@ -1,15 +1,21 @@
 mod builtins;
 
 use lox_bytecode::bytecode::Module;
+use lox_compiler::Diagnostic;
 use crate::value::Value;
 use builtins::Builtins;
 
 use super::memory::*;
 use super::interner::{Symbol, Interner};
-use lox_gc::{Gc, Trace, Tracer};
+use lox_gc::{Gc, HeapConfig, Trace, Weak};
+use std::cell::Cell;
 use crate::fiber::Fiber;
 use crate::string::LoxString;
 use std::collections::HashMap;
+use std::sync::Arc;
+use crate::Native;
+use crate::native_module::{NativeModule, NativeModuleBuilder};
+use crate::loader::{compile_with_natives, CircularImport, ImportError, ImportSite, MemoryLoader, ModuleId, ModuleLoadError, ModuleLoader, ModuleSource};
 
 #[derive(PartialEq, Eq, Copy, Clone)]
 pub enum Signal {
@@ -20,7 +26,7 @@ pub enum Signal {
 
 //TODO thiserror
 //TODO RuntimeError
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum VmError {
     Unknown,
     StackEmpty,
@@ -35,40 +41,92 @@ pub enum VmError {
     UndefinedProperty,
     Unimplemented,
     UnknownImport,
+    /// An imported module could not be found or read.
+    Load(Box<ModuleLoadError>),
+    /// A module imported itself, directly or through other modules, before it finished loading.
+    CircularImport(Box<CircularImport>),
+    /// A global was imported that its module does not export.
+    NotExported,
     IndexOutOfRange,
+    /// An imported module does not compile.
+    Import(Box<ImportError>),
+    /// The OS could not do what a native function asked, with the kind of error and its message.
+    Io(std::io::ErrorKind, String),
+    /// The script asked to stop with this exit status, such as with `os.exit(code)`.
+    Exit(i32),
+    /// Text given to a native function could not be parsed, with the reason and where.
+    Parse(String),
+    /// A native function or a worker thread panicked, with the panic message.
+    Panic(String),
 }
 
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmError::Import(err) => write!(f, "could not import {}", err.module),
+            VmError::CircularImport(err) => write!(f, "{err}"),
+            VmError::Load(err) => write!(f, "{err}"),
+            VmError::Io(_, message) => write!(f, "{message}"),
+            VmError::Exit(code) => write!(f, "exited with status {code}"),
+            VmError::Parse(message) => write!(f, "{message}"),
+            VmError::Panic(message) => write!(f, "panicked: {message}"),
+            err => write!(f, "{err:?}"),
+        }
+    }
+}
+
+impl std::error::Error for VmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VmError::Import(err) => Some(&**err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Trace)]
 pub struct Runtime {
     pub fiber: Fiber,
     init_symbol: Symbol, //TODO Move to builtins
+    /// `Class.name` reads the name of a class.
+    pub(crate) name_symbol: Symbol,
+    #[trace(skip)]
     pub interner: Interner,
     pub imports: HashMap<LoxString, Gc<Import>>,
 
     pub builtins: Builtins,
 
+    /// Objects with a Lox callback to run once they are collected.
+    finalizers: Vec<(Weak<()>, Value)>,
+    /// Callbacks of collected objects, waiting for a safe point.
+    pending_finalizers: Vec<Value>,
+    /// Set after a collection, so the next safe point looks for collected objects.
+    finalize_pending: Cell<bool>,
+    collections: Cell<usize>,
+
+    /// `interpret` returns once a `RETURN` leaves this many frames.
+    pub(crate) exit_depth: usize,
+
     // Env
+    #[trace(skip)]
     pub print: for<'r> fn(&'r str),
-    pub import: for<'r> fn(&'r str) -> Option<Module>,
-
+    #[trace(skip)]
+    pub loader: Arc<dyn ModuleLoader>,
+    /// Modules written in Rust by name, built when they are first imported.
+    #[trace(skip)]
+    pub native_modules: HashMap<String, NativeModuleBuilder>,
+    /// The heap of the VMs that native functions start on other threads, such as `thread.spawn`.
+    #[trace(skip)]
+    pub worker_heap_config: HeapConfig,
+
+    #[trace(skip)]
     ip: *const u8,
 }
 
-unsafe impl Trace for Runtime {
-    #[inline]
-    fn trace(&self, tracer: &mut Tracer) {
-        self.fiber.trace(tracer);
-        self.imports.trace(tracer);
-        self.builtins.trace(tracer);
-    }
-}
-
 pub fn default_print(value: &str) {
     println!("{}", value);
 }
 
-pub fn default_import(_value: &str) -> Option<Module> {
-    None
-}
 
 impl Runtime {
     pub fn new() -> Self {
@@ -78,13 +136,23 @@ impl Runtime {
         Self {
             fiber: Fiber::new(),
             init_symbol: interner.intern("init"),
+            name_symbol: interner.intern("name"),
             interner,
             imports: HashMap::new(),
             print: default_print,
-            import: default_import,
+            loader: Arc::new(MemoryLoader::new()),
+            native_modules: HashMap::new(),
+            worker_heap_config: HeapConfig::default(),
 
             builtins,
 
+            finalizers: Vec::new(),
+            pending_finalizers: Vec::new(),
+            finalize_pending: Cell::new(false),
+            collections: Cell::new(0),
+
+            exit_depth: 0,
+
             ip: std::ptr::null(),
         }
     }
@@ -105,8 +173,10 @@ impl Runtime {
         (self.print)(value);
     }
 
-    pub fn with_module(&mut self, module: Module) {
-        let closure = self.prepare_interpret(module);
+    pub fn with_module(&mut self, name: &str, module: Module) {
+        // A failed run leaves its frames behind.
+        self.fiber.unwind();
+        let closure = self.prepare_interpret(name, module);
         self.fiber.stack.push(Value::from_object(closure.erase()));
         self.fiber.begin_frame(closure);
         self.load_ip();
@@ -115,14 +185,88 @@ impl Runtime {
     #[cold]
     pub fn manage<T: Trace + 'static>(&self, data: T) -> Gc<T> {
         lox_gc::collect(&[self, &data]);
+        self.after_collect();
         lox_gc::manage(data)
     }
 
-    //TODO Use name given instead of _root
-    fn prepare_interpret(&mut self, module: Module) -> Gc<Closure> {
-        let import = Import::with_module("_root", module, &mut self.interner);
+    pub fn collect(&self) {
+        lox_gc::force_collect(&[self]);
+        self.after_collect();
+    }
+
+    fn after_collect(&self) {
+        let collections = lox_gc::collections();
+        if collections != self.collections.get() {
+            self.collections.set(collections);
+            self.finalize_pending.set(!self.finalizers.is_empty());
+        }
+    }
+
+    /// Call `callback` with no arguments at a safe point after `object` has been collected.
+    pub fn add_finalizer(&mut self, object: Gc<()>, callback: Value) {
+        let object = lox_gc::downgrade(object);
+        self.finalizers.push((object, callback));
+    }
+
+    /// Run the callbacks of collected objects. Must only be called between instructions.
+    #[inline]
+    pub fn run_finalizers(&mut self) -> Result<(), VmError> {
+        if self.finalize_pending.get() {
+            self.run_pending_finalizers()
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cold]
+    fn run_pending_finalizers(&mut self) -> Result<(), VmError> {
+        self.finalize_pending.set(false);
+
+        let pending = &mut self.pending_finalizers;
+        self.finalizers.retain(|(object, callback)| {
+            if object.upgrade().is_some() {
+                true
+            } else {
+                pending.push(*callback);
+                false
+            }
+        });
+
+        while let Some(callback) = self.pending_finalizers.pop() {
+            self.call_value(callback, &[])?;
+        }
+
+        Ok(())
+    }
+
+    /// Call `callee` from native code and run it to completion.
+    /// This must happen between instructions of a running frame.
+    pub fn call_value(&mut self, callee: Value, args: &[Value]) -> Result<Value, VmError> {
+        let ip = self.ip;
+        let depth = self.fiber.frame_count();
+
+        self.fiber.stack.push(callee);
+        for arg in args {
+            self.fiber.stack.push(*arg);
+        }
+
+        let exit_depth = std::mem::replace(&mut self.exit_depth, depth);
+        let result = match self.call(args.len(), callee) {
+            Signal::RuntimeError => Err(self.fiber.take_error().unwrap_or(VmError::Unknown)),
+            _ if self.fiber.frame_count() > depth => self.interpret(),
+            _ => Ok(()),
+        };
+        self.exit_depth = exit_depth;
+        self.ip = ip;
+
+        result?;
+        Ok(self.fiber.stack.pop())
+    }
+
+    fn prepare_interpret(&mut self, name: &str, module: Module) -> Gc<Closure> {
+        let import = Import::with_module(name, module, &mut self.interner);
         let import: Gc<Import> = self.manage(import.into());
-        self.imports.insert(import.name.clone(), import);
+        self.add_import(import);
         self.globals_import().copy_to(&import);
 
         self.manage(Closure::with_import(import).into())
@@ -136,20 +280,177 @@ impl Runtime {
         }
     }
 
-    pub fn load_import(&mut self, path: &str) -> Result<Gc<Import>, VmError> {
-        let module = (self.import)(path);
-        if let Some(module) = module {
-            let import = Import::with_module(path, module, &mut self.interner);
-            let import = self.manage(import.into());
-            self.imports.insert(path.into(), import);
-            self.globals_import().copy_to(&import);
+    /// The module `path` refers to, resolved relative to the running module.
+    pub fn resolve_import(&self, path: &str) -> Result<ModuleId, VmError> {
+        if self.native_modules.contains_key(path) {
+            return Ok(ModuleId::new(path));
+        }
 
-            Ok(import)
-        } else {
-            Err(VmError::UnknownImport)
+        let importer = self.current_module();
+        self.loader.resolve(&importer, path).map_err(|err| {
+            VmError::Load(Box::new(ModuleLoadError {
+                module: None,
+                site: Some(ImportSite {
+                    importer,
+                    path: path.to_string(),
+                }),
+                error: Arc::new(err),
+            }))
+        })
+    }
+
+    fn current_module(&self) -> ModuleId {
+        let import = self.fiber.has_current_frame().then(|| self.fiber.current_import());
+        ModuleId::new(import.as_ref().map_or("", |import| import.name.as_str()))
+    }
+
+    /// The error for importing `import`, which `path` resolved to, before it finished loading.
+    pub(crate) fn circular_import(&self, path: &str, import: Gc<Import>) -> VmError {
+        let importer = self.current_module();
+
+        let mut cycle = vec![importer.clone()];
+        for loading in self.fiber.loading() {
+            let id = ModuleId::new(loading.name.as_str());
+            if cycle.last() != Some(&id) {
+                cycle.push(id);
+            }
+
+            if Gc::ptr_eq(loading, import) {
+                break;
+            }
+        }
+
+        VmError::CircularImport(Box::new(CircularImport {
+            cycle,
+            site: ImportSite {
+                importer,
+                path: path.to_string(),
+            },
+        }))
+    }
+
+    /// Compile `source`, the module `id`, checking its imports against the native modules and the loader.
+    pub fn compile_source(&self, id: &ModuleId, source: &str) -> Result<Module, Vec<Diagnostic>> {
+        compile_with_natives(&*self.loader, |path| self.native_modules.contains_key(path), id, source)
+    }
+
+    /// Load and compile the module `id`, which `path` resolved to, without running it.
+    pub fn load_module(&self, path: &str, id: &ModuleId) -> Result<Module, VmError> {
+        let site = ImportSite {
+            importer: self.current_module(),
+            path: path.to_string(),
+        };
+
+        self.compile_module(id, Some(site))
+    }
+
+    fn compile_module(&self, id: &ModuleId, site: Option<ImportSite>) -> Result<Module, VmError> {
+        let source = match self.loader.load(id) {
+            Ok(ModuleSource::Module(module)) => return Ok(module),
+            Ok(ModuleSource::Source(source)) => source,
+            Err(err) => {
+                return Err(VmError::Load(Box::new(ModuleLoadError {
+                    module: Some(id.clone()),
+                    site,
+                    error: Arc::new(err),
+                })));
+            },
+        };
+
+        self.compile_source(id, &source).map_err(|diagnostics| {
+            VmError::Import(Box::new(ImportError {
+                module: id.clone(),
+                site,
+                source,
+                diagnostics,
+            }))
+        })
+    }
+
+    /// Build the native module registered as `id`, if there is one.
+    pub fn load_native_module(&mut self, id: &ModuleId) -> Option<Gc<Import>> {
+        let build = self.native_modules.get(id.as_str())?.clone();
+
+        let import = self.manage(Import::new(id.as_str()));
+        self.add_import(import);
+        build(&mut NativeModule::new(&mut Native { runtime: self }, import));
+
+        Some(import)
+    }
+
+    pub fn load_import(&mut self, path: &str, id: &ModuleId) -> Result<Gc<Import>, VmError> {
+        let module = self.load_module(path, id)?;
+        let import = Import::with_module(id.as_str(), module, &mut self.interner);
+        let import = self.manage(import.into());
+        self.add_import(import);
+        self.globals_import().copy_to(&import);
+
+        Ok(import)
+    }
+
+    /// Compile the module `id` again and run its new top level, which replaces the globals it defines.
+    /// Classes keep their identity and get the methods of the new class, so existing instances use the new methods.
+    /// If the new top level fails, the old version stays with the globals it had.
+    pub fn reload_module(&mut self, id: &ModuleId) -> Result<(), VmError> {
+        let old = self.import(id.as_str()).ok_or(VmError::UnknownImport)?;
+        let module = self.compile_module(id, None)?;
+        self.fiber.unwind();
+
+        // Rooted, because the new top level replaces them in the globals.
+        let globals = old.global_entries();
+        lox_gc::with_root(&globals, || {
+            let import = Import::reloaded(&old, module, &mut self.interner);
+            let import = self.manage(import);
+
+            let closure = lox_gc::with_root(&import, || self.manage(Closure::with_import(import)));
+            self.fiber.stack.push(Value::from_object(closure.erase()));
+            self.fiber.begin_frame(closure);
+            self.load_ip();
+
+            if let Err(err) = self.interpret() {
+                self.fiber.unwind();
+                old.restore_globals(&globals);
+                return Err(err);
+            }
+
+            self.add_import(import);
+
+            for &(name, value) in &globals {
+                let Some(class) = value.try_cast::<Class>() else {
+                    continue;
+                };
+                let Some(new) = import.global(name).and_then(|value| value.try_cast::<Class>()) else {
+                    continue;
+                };
+
+                if !Gc::ptr_eq(class, new) {
+                    class.replace_methods(&new);
+                    import.set_global(name, Value::from_object(class.erase()));
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Forget the imported modules whose top level was interrupted by an error, so that importing them again
+    /// runs them again instead of finding them half initialized. The first frame runs the module given to
+    /// `interpret_as`, which stays so that it can be reloaded.
+    pub(crate) fn discard_failed_imports(&mut self) {
+        let failed: Vec<_> = self.fiber.loading_from(self.exit_depth.max(1)).collect();
+        for import in failed {
+            if self.imports.get(&import.name).is_some_and(|cached| Gc::ptr_eq(*cached, import)) {
+                self.imports.remove(&import.name);
+            }
         }
     }
 
+    pub fn add_import(&mut self, import: Gc<Import>) {
+        // Cloning the name allocates, and `import` is usually not reachable yet.
+        let name = lox_gc::with_root(&import, || import.name.clone());
+        self.imports.insert(name, import);
+    }
+
     pub fn globals_import(&self) -> Gc<Import> {
         self.builtins.globals_import
     }
@@ -190,9 +491,16 @@ impl Runtime {
     pub fn call_native_function(&mut self, arity: usize, callee: Gc<NativeFunction>) -> Signal {
         self.store_ip();
 
-        let args = self.fiber.stack.pop_n(arity);
-        let this = self.fiber.stack.pop(); // discard callee
-        let result = (callee.code)(this, &args);
+        // The arguments stay on the stack, so they are rooted during the call.
+        let base = self.fiber.stack.len() - arity - 1;
+        let args = self.fiber.stack.peek_slice(arity);
+        let this = self.fiber.stack.peek_n(arity);
+        let result = (callee.code)(&mut Native { runtime: self }, this, &args);
+        let result = match result {
+            Ok(result) => result,
+            Err(error) => return self.fiber.runtime_error(error),
+        };
+        self.fiber.stack.truncate(base);
         self.fiber.stack.push(result);
 
         self.load_ip();
@@ -226,6 +534,12 @@ impl Runtime {
             return self.fiber.runtime_error(VmError::IncorrectArity);
         }
 
+        // Declared fields are set first, so their frame goes on top of the one of `init`.
+        if let Some(fields) = class.initializer() {
+            self.fiber.stack.push(Value::from_object(instance.erase()));
+            self.fiber.begin_frame(fields);
+        }
+
         self.load_ip();
 
         Signal::More
@@ -245,7 +559,6 @@ impl Runtime {
         self.fiber.stack.push(Value::from_object(root.erase()));
     }
 
-
     #[inline]
     pub fn next_u32(&mut self) -> u32 {
         unsafe {
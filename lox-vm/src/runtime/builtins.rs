@@ -1,11 +1,15 @@
-use lox_gc::{Gc, Trace, Tracer};
+use lox_gc::{Gc, Trace};
 use crate::memory::{Import, Class};
+use std::any::TypeId;
+use std::collections::HashMap;
 
+#[derive(Trace)]
 pub struct Builtins {
     pub empty_class: Gc<Class>,
     pub list_class: Gc<Class>,
     pub string_class: Gc<Class>,
     pub globals_import: Gc<Import>,
+    pub native_classes: HashMap<TypeId, Gc<Class>>,
 }
 
 impl Builtins {
@@ -15,6 +19,7 @@ impl Builtins {
             globals_import: lox_gc::manage(Import::new("globals").into()),
             list_class: lox_gc::manage(Class::new("List".to_string()).into()),
             string_class: lox_gc::manage(Class::new("String".to_string()).into()),
+            native_classes: HashMap::new(),
         }
     }
 
@@ -27,17 +32,10 @@ impl Builtins {
             self.list_class
         } else if object.is::<String>() {
             self.string_class
+        } else if let Some(class) = self.native_classes.get(&object.type_id()) {
+            *class
         } else {
             self.empty_class
         }
     }
 }
-
-unsafe impl Trace for Builtins {
-    fn trace(&self, tracer: &mut Tracer) {
-        self.string_class.trace(tracer);
-        self.empty_class.trace(tracer);
-        self.list_class.trace(tracer);
-        self.globals_import.trace(tracer);
-    }
-}
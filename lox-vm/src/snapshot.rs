@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use lox_gc::Gc;
+
+use crate::memory::{Class, Closure, Globals, Import, Instance, NativeFunction};
+use crate::string::LoxString;
+use crate::value::Value;
+
+/// What is filling the heap, taken by [`crate::VirtualMachine::heap_snapshot`].
+#[derive(Clone, Debug, Default)]
+pub struct HeapSnapshot {
+    pub objects: usize,
+    pub bytes: usize,
+    /// Objects per kind, such as `List` or `Instance`, most bytes first.
+    pub kinds: Vec<KindStats>,
+    /// Instances per Lox class name, most bytes first.
+    pub classes: Vec<KindStats>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KindStats {
+    pub name: String,
+    pub count: usize,
+    /// Bytes of the objects themselves and the buffers they own.
+    pub bytes: usize,
+}
+
+impl HeapSnapshot {
+    pub(crate) fn new(snapshot: &lox_gc::Snapshot) -> Self {
+        let mut kinds = HashMap::new();
+        let mut classes = HashMap::new();
+
+        for object in &snapshot.objects {
+            add(&mut kinds, kind_name(object.type_name), object.bytes);
+
+            if let Some(instance) = object.object.try_cast::<Instance>() {
+                add(&mut classes, instance.class.name.as_str(), object.bytes);
+            }
+        }
+
+        Self {
+            objects: snapshot.objects.len(),
+            bytes: snapshot.objects.iter().map(|object| object.bytes).sum(),
+            kinds: sorted(kinds),
+            classes: sorted(classes),
+        }
+    }
+}
+
+fn add(stats: &mut HashMap<String, KindStats>, name: &str, bytes: usize) {
+    let stats = stats.entry(name.to_string()).or_insert_with(|| KindStats {
+        name: name.to_string(),
+        count: 0,
+        bytes: 0,
+    });
+
+    stats.count += 1;
+    stats.bytes += bytes;
+}
+
+fn sorted(stats: HashMap<String, KindStats>) -> Vec<KindStats> {
+    let mut stats: Vec<_> = stats.into_values().collect();
+    stats.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    stats
+}
+
+/// The type name without its module path, so `lox_vm::memory::list::List` becomes `List`.
+fn kind_name(type_name: &str) -> &str {
+    let path_end = type_name.find('<').unwrap_or(type_name.len());
+    let start = type_name[..path_end].rfind("::").map_or(0, |index| index + 2);
+    &type_name[start..]
+}
+
+/// Describe the chain of objects that keeps `value` alive, starting at a root.
+pub(crate) fn retained_by(value: Value) -> Option<Vec<String>> {
+    if !value.is_object() {
+        return None;
+    }
+
+    // Every runtime is a registered root.
+    let snapshot = lox_gc::snapshot_retaining(&[], value.as_object());
+    let index = snapshot.find(value.as_object())?;
+    let path = snapshot.retained_by(index)
+        .into_iter()
+        // The module before them already says whose globals they are.
+        .filter(|index| !snapshot.objects[*index].object.is::<Globals>())
+        .map(|index| {
+            let object = &snapshot.objects[index];
+            describe(object.object, object.type_name)
+        })
+        .collect();
+
+    Some(path)
+}
+
+/// A short description of `object`, as shown in a retained-by path.
+fn describe(object: Gc<()>, type_name: &str) -> String {
+    if let Some(instance) = object.try_cast::<Instance>() {
+        format!("{} instance", instance.class.name)
+    } else if let Some(class) = object.try_cast::<Class>() {
+        format!("class {}", class.name)
+    } else if let Some(closure) = object.try_cast::<Closure>() {
+        format!("fn {}", closure.function.name)
+    } else if let Some(native) = object.try_cast::<NativeFunction>() {
+        format!("native fn {}", native.name)
+    } else if let Some(import) = object.try_cast::<Import>() {
+        format!("module {}", import.name)
+    } else if object.is::<LoxString>() {
+        "String".to_string()
+    } else {
+        kind_name(type_name).to_string()
+    }
+}
+
@@ -102,10 +102,10 @@ impl Stack {
         }
     }
 
-    pub fn pop_n(&mut self, n: usize) -> Vec<Value> {
+    /// Copy the top `n` values, leaving them on the stack.
+    pub fn peek_slice(&self, n: usize) -> Vec<Value> {
         unsafe {
-            self.top = self.top.sub(n);
-            let slice = std::ptr::slice_from_raw_parts(self.top, n);
+            let slice = std::ptr::slice_from_raw_parts(self.top.sub(n), n);
             (*slice).into()
         }
     }
@@ -1,20 +1,15 @@
 use super::value::Value;
 use super::interner::Symbol;
-use lox_gc::{Trace, Tracer};
+use lox_gc::Trace;
 use crate::array::Array;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Trace)]
 struct Entry {
     key: Symbol,
     value: Value,
 }
 
-unsafe impl Trace for Entry {
-    fn trace(&self, tracer: &mut Tracer) {
-        self.value.trace(tracer);
-    }
-}
-
+#[derive(Trace)]
 pub struct Table {
     count: usize,
     capacity: usize,
@@ -22,12 +17,6 @@ pub struct Table {
     entries: Array<Entry>,
 }
 
-unsafe impl Trace for Table {
-    fn trace(&self, tracer: &mut Tracer) {
-        self.entries.trace(tracer);
-    }
-}
-
 impl Default for Table {
     fn default() -> Self {
         Self::new()
@@ -63,6 +52,22 @@ impl Table {
         }
     }
 
+    /// Remove all entries, keeping the capacity.
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            entry.key = Symbol::invalid();
+            entry.value = Value::NIL;
+        }
+
+        self.count = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, Value)> + '_ {
+        self.entries.iter()
+            .filter(|entry| entry.key != Symbol::invalid())
+            .map(|entry| (entry.key, entry.value))
+    }
+
     #[inline]
     pub fn has(&self, key: Symbol) -> bool {
         if self.count == 0 {
@@ -78,7 +83,8 @@ impl Table {
     #[inline]
     pub fn set(&mut self, key: Symbol, value: Value) -> bool {
         if self.count + 1 > self.max_capacity {
-            self.adjust_capacity();
+            // `value` might not be reachable from anywhere else yet.
+            lox_gc::with_root(&value, || self.adjust_capacity());
         }
 
         let index = find_entry(self.capacity, &self.entries, key);
@@ -90,6 +96,8 @@ impl Table {
             self.count += 1;
         }
 
+        lox_gc::write_barrier(&value);
+
         entry.key = key;
         entry.value = value;
 
@@ -13,7 +13,7 @@ fn execute(source: &str) -> TestResult {
         Err(_) => return TestResult::CompileError,
     };
 
-    let mut vm = lox_vm::VirtualMachine::new();
+    let mut vm = lox_vm::VirtualMachine::new().unwrap();
     lox_std::set_stdlib(&mut vm);
     let result = match vm.interpret(module) {
         Ok(_) => TestResult::Ok,
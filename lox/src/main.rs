@@ -1,26 +1,95 @@
+use std::collections::HashMap;
 use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use lox_compiler::LineOffsets;
-use lox_vm::VirtualMachine;
-use lox_std::set_stdlib;
-use lox_bytecode::bytecode::Module;
+use lox_vm::{DirectoryLoader, HeapConfig, HeapSnapshot, KindStats, ModuleId, VirtualMachine, VmError};
+use lox_std::{set_args, set_stdlib};
 
 #[cfg(test)]
 mod tests;
 
 fn main() {
-    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // Everything after the path of the script is for the script.
+    let script_args = match args.iter().position(|arg| !arg.starts_with("--") && arg != "watch") {
+        Some(path) => args.split_off(path + 1),
+        None => Vec::new(),
+    };
+
+    let gc_stats = args.iter().any(|arg| arg == "--gc-stats");
+    args.retain(|arg| arg != "--gc-stats");
+
+    let mut heap_config = HeapConfig::default();
+    if let Some(arg) = args.iter().find_map(|arg| arg.strip_prefix("--heap-size=")) {
+        match arg.parse() {
+            Ok(bytes) => heap_config.reserve_bytes = bytes,
+            Err(_) => {
+                eprintln!("Error: invalid heap size '{arg}'");
+                return;
+            },
+        }
+    }
+    args.retain(|arg| !arg.starts_with("--heap-size="));
+
+    let heap_snapshot = args.iter()
+        .find_map(|arg| arg.strip_prefix("--heap-snapshot="))
+        .map(String::from);
+    args.retain(|arg| !arg.starts_with("--heap-snapshot="));
+
+    // Searched for imports that are not next to the importing file.
+    let mut search_paths: Vec<PathBuf> = args.iter()
+        .filter_map(|arg| arg.strip_prefix("--lib="))
+        .map(PathBuf::from)
+        .collect();
+    args.retain(|arg| !arg.starts_with("--lib="));
+    if let Some(paths) = env::var_os("LOX_PATH") {
+        search_paths.extend(env::split_paths(&paths));
+    }
+
+    // `lox watch <path>` keeps running, and reloads every module whose file changes.
+    let watch = args.len() == 2 && args[0] == "watch";
+    if watch {
+        args.remove(0);
+    }
 
     if args.len() != 1 {
-        eprintln!("Usage: lox [path]");
+        eprintln!("Usage: lox [watch] [--gc-stats] [--heap-size=<bytes>] [--heap-snapshot=<file>] [--lib=<dir>]... [path] [args]...");
         return;
     }
 
     let path = args.first().unwrap();
-    let data = std::fs::read_to_string(path).unwrap();
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Error: could not read {path}: {err}");
+            return;
+        },
+    };
     let offsets = LineOffsets::new(&data);
 
-    let module = match lox_compiler::compile(&data) {
+    let loader = DirectoryLoader::new(search_paths);
+    // Imports are resolved relative to the script, under the same names as the modules it imports.
+    let name = match Path::new(path).canonicalize() {
+        Ok(name) => name.to_string_lossy().into_owned(),
+        Err(_) => path.clone(),
+    };
+
+    // Run virtual machine
+    let mut vm = match VirtualMachine::with_heap_config(heap_config) {
+        Ok(vm) => vm,
+        Err(err) => {
+            eprintln!("Error: could not reserve the heap: {err}");
+            return;
+        },
+    };
+    set_stdlib(&mut vm);
+    set_args(&mut vm, script_args);
+    vm.set_loader(loader);
+
+    let module = match vm.compile_module(&ModuleId::new(&*name), &data) {
         Ok(module) => module,
         Err(diagnostics) => {
             for diag in diagnostics {
@@ -32,27 +101,103 @@ fn main() {
         },
     };
 
-    // Run virtual machine
-    let mut vm = VirtualMachine::new();
-    set_stdlib(&mut vm);
-    vm.set_import(import);
-    vm.interpret(module).unwrap();
+    let result = vm.interpret_as(&name, module);
+
+    if gc_stats {
+        print_gc_stats(&lox_gc::stats());
+    }
+
+    if let Some(path) = heap_snapshot {
+        let json = heap_snapshot_json(&vm.heap_snapshot());
+        if let Err(err) = std::fs::write(&path, json.to_string()) {
+            eprintln!("Error: could not write heap snapshot to {path}: {err}");
+        }
+    }
+
+    if let Err(VmError::Exit(code)) = result {
+        std::process::exit(code);
+    }
+
+    if let Err(err) = result {
+        print_error(&err);
+        if !watch {
+            std::process::exit(70);
+        }
+    }
+
+    if watch {
+        watch_modules(&mut vm);
+    }
 }
 
-fn import(path: &str) -> Option<Module> {
-    let data = std::fs::read_to_string(format!("{}.lox", path)).unwrap();
-    let offsets = LineOffsets::new(&data);
+fn print_error(err: &VmError) {
+    eprintln!("Error: {err}");
+    let mut source = err.source();
+    while let Some(err) = source {
+        eprintln!("  {err}");
+        source = err.source();
+    }
+}
 
-    match lox_compiler::compile(&data) {
-        Ok(module) => Some(module),
-        Err(diagnostics) => {
-            for diag in diagnostics {
-                let line = offsets.line(diag.span.start);
-                let msg = diag.message;
-                eprintln!("Error: {msg} at line {line}");
+/// Reload the modules whose file changed since the last check, forever.
+fn watch_modules(vm: &mut VirtualMachine) -> ! {
+    let mut modified = HashMap::new();
+
+    loop {
+        for id in vm.modules() {
+            // Modules that are not files are not watched.
+            let Ok(time) = std::fs::metadata(id.as_str()).and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+
+            match modified.insert(id.clone(), time) {
+                Some(previous) if previous != time => match vm.reload_module(&id) {
+                    Ok(()) => eprintln!("Reloaded {id}"),
+                    Err(err) => print_error(&err),
+                },
+                _ => {},
             }
+        }
 
-            None
-        },
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+fn print_gc_stats(stats: &lox_gc::Stats) {
+    eprintln!("GC statistics:");
+    eprintln!("  collections:     {}", stats.collections);
+    eprintln!("  bytes allocated: {}", stats.bytes_allocated);
+    eprintln!("  bytes freed:     {}", stats.bytes_freed);
+    eprintln!("  bytes released:  {}", stats.bytes_released);
+    eprintln!("  live bytes:      {}", stats.live_bytes);
+    eprintln!("  next threshold:  {}", stats.threshold);
+    eprintln!("  pauses:          {}", stats.pauses);
+    eprintln!("  total pause:     {:?}", stats.total_pause);
+    eprintln!("  max pause:       {:?}", stats.max_pause);
+    eprintln!("  live bytes per size class:");
+    for class in &stats.classes {
+        match class.block_bytes {
+            Some(bytes) => eprintln!("    {:>5} B blocks: {}", bytes, class.live_bytes),
+            None => eprintln!("    reams:          {}", class.live_bytes),
+        }
     }
 }
+
+fn heap_snapshot_json(snapshot: &HeapSnapshot) -> serde_json::Value {
+    let stats = |stats: &[KindStats]| {
+        stats.iter()
+            .map(|stats| serde_json::json!({
+                "name": stats.name,
+                "count": stats.count,
+                "bytes": stats.bytes,
+            }))
+            .collect::<Vec<_>>()
+    };
+
+    serde_json::json!({
+        "objects": snapshot.objects,
+        "bytes": snapshot.bytes,
+        "kinds": stats(&snapshot.kinds),
+        "classes": stats(&snapshot.classes),
+    })
+}
@@ -25,21 +25,24 @@ thread_local! {
 }
 
 //TODO Handle errors
-fn execute(source: &str) -> (Vec<String>, TestResult) {
-    let module = match lox_compiler::compile(source) {
-        Ok(module) => module,
-        Err(_) => return (vec![], TestResult::CompileError),
-    };
-
+fn execute(source: &str, collector: lox_vm::Collector) -> (Vec<String>, TestResult) {
     fn print(value: &str) {
         DATA.with(|data| {
             data.lock().unwrap().push(value.into());
         });
     }
 
-    let mut vm = lox_vm::VirtualMachine::new();
+    let mut vm = lox_vm::VirtualMachine::with_collector(collector).unwrap();
     vm.set_stdout(print);
+    vm.set_loader(loader());
     lox_std::set_stdlib(&mut vm);
+    register_modules(&mut vm);
+
+    let module = match vm.compile_module(&lox_vm::ModuleId::new("_root"), source) {
+        Ok(module) => module,
+        Err(_) => return (vec![], TestResult::CompileError),
+    };
+
     let result = match vm.interpret(module) {
         Ok(_) => TestResult::Ok,
         Err(err) => {
@@ -60,7 +63,45 @@ fn execute(source: &str) -> (Vec<String>, TestResult) {
     (output.lines().map(|l| l.to_owned()).collect(), result)
 }
 
+fn loader() -> lox_vm::MemoryLoader {
+    lox_vm::MemoryLoader::new()
+        .with("thread/echo_worker", include_str!("thread/echo_worker.lox"))
+        .with("thread/error_worker", include_str!("thread/error_worker.lox"))
+        .with("import/circular_a", include_str!("import/circular_a.lox"))
+        .with("import/circular_b", include_str!("import/circular_b.lox"))
+        .with("import/exports", include_str!("import/exports.lox"))
+        .with("import/broken", include_str!("import/broken.lox"))
+        .with("import/shadowed", include_str!("import/shadowed.lox"))
+}
+
+/// Native modules for the import tests.
+fn register_modules(vm: &mut lox_vm::VirtualMachine) {
+    vm.register_module("native/greeting", |m| {
+        m.function("greet", |native, _this, args| {
+            let name = match args {
+                [name] => name.try_cast::<lox_vm::string::LoxString>().ok_or(lox_vm::VmError::UnexpectedValue)?,
+                _ => return Err(lox_vm::VmError::IncorrectArity),
+            };
+
+            Ok(native.string(&format!("hello {}", name.as_str())))
+        });
+        m.constant("answer", 42.0);
+
+        let name = m.native().string("lox");
+        m.constant("name", name);
+    });
+
+    vm.register_module("import/shadowed", |m| {
+        let source = m.native().string("native");
+        m.constant("source", source);
+    });
+}
+
 fn harness(source: &str) {
+    harness_with_collector(source, lox_vm::Collector::StopTheWorld);
+}
+
+fn harness_with_collector(source: &str, collector: lox_vm::Collector) {
     let expects = parse_expects(source, Regex::new(r"// expect: ?(.*)").unwrap(), 1);
 
     let expected_result =
@@ -80,7 +121,7 @@ fn harness(source: &str) {
             TestResult::Ok
         };
 
-    let (output, result) = execute(source);
+    let (output, result) = execute(source, collector);
     assert_eq!(expects, output);
     assert_eq!(expected_result, result);
 }
@@ -95,6 +136,789 @@ fn unexpected_character() {
     harness(include_str!("unexpected_character.lox"));
 }
 
+mod gc {
+    use super::harness;
+    use super::harness_with_collector;
+    use lox_vm::Collector;
+
+    #[test]
+    fn collect() {
+        harness(include_str!("gc/collect.lox"));
+    }
+
+    #[test]
+    fn collect_arity() {
+        harness(include_str!("gc/collect_arity.lox"));
+    }
+
+    #[test]
+    fn retained_by() {
+        harness(include_str!("gc/retained_by.lox"));
+    }
+
+    #[test]
+    fn retained_by_arity() {
+        harness(include_str!("gc/retained_by_arity.lox"));
+    }
+
+    #[test]
+    fn heap_snapshot() {
+        let module = lox_compiler::compile(include_str!("gc/retained_by.lox")).unwrap();
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|_| ());
+        lox_std::set_stdlib(&mut vm);
+        vm.interpret(module).unwrap();
+
+        let snapshot = vm.heap_snapshot();
+        let nodes = snapshot.classes.iter().find(|class| class.name == "Node").unwrap();
+        assert_eq!(nodes.count, 2);
+        let instances = snapshot.kinds.iter().find(|kind| kind.name == "Instance").unwrap();
+        assert_eq!(instances.count, 3);
+        assert_eq!(snapshot.objects, snapshot.kinds.iter().map(|kind| kind.count).sum::<usize>());
+    }
+
+    #[test]
+    fn root() {
+        use std::cell::RefCell;
+        use lox_vm::Root;
+        use lox_vm::value::Value;
+
+        thread_local! {
+            static KEPT: RefCell<Option<Root<Value>>> = const { RefCell::new(None) };
+        }
+
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|value| super::DATA.with(|data| data.lock().unwrap().push(value.into())));
+        lox_std::set_stdlib(&mut vm);
+        vm.native().set_global_fn("keep", |native, _this, args| {
+            let root = native.root(args[0]);
+            KEPT.with(|kept| *kept.borrow_mut() = Some(root));
+            Ok(Value::NIL)
+        });
+
+        let module = lox_compiler::compile(include_str!("gc/root_keep.lox")).unwrap();
+        vm.interpret(module).unwrap();
+        // Only the root keeps the callback and its upvalue alive now.
+        vm.native().collect().unwrap();
+
+        let callback = KEPT.with(|kept| kept.borrow().as_ref().unwrap().get());
+        vm.native().set_global("callback", callback);
+
+        let source = include_str!("gc/root_call.lox");
+        let module = lox_compiler::compile(source).unwrap();
+        vm.interpret(module).unwrap();
+
+        let output = super::DATA.with(|data| std::mem::take(&mut *data.lock().unwrap()));
+        let expects = super::parse_expects(source, regex::Regex::new(r"// expect: ?(.*)").unwrap(), 1);
+        assert_eq!(expects, output);
+
+        KEPT.with(|kept| kept.borrow_mut().take());
+    }
+
+    #[test]
+    #[cfg_attr(feature = "gc-stress", ignore = "collects on every allocation, which takes minutes")]
+    fn stop_the_world() {
+        harness(include_str!("gc/incremental.lox"));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "gc-stress", ignore = "collects on every allocation, which takes minutes")]
+    fn incremental() {
+        harness_with_collector(include_str!("gc/incremental.lox"), Collector::Incremental);
+    }
+}
+
+mod weak {
+    use super::harness;
+    use super::harness_with_collector;
+    use lox_vm::Collector;
+
+    #[test]
+    fn on_collect() {
+        harness(include_str!("weak/on_collect.lox"));
+    }
+
+    #[test]
+    fn on_collect_automatic() {
+        harness(include_str!("weak/on_collect_automatic.lox"));
+    }
+
+    #[test]
+    fn on_collect_incremental() {
+        harness_with_collector(include_str!("weak/on_collect_automatic.lox"), Collector::Incremental);
+    }
+
+    #[test]
+    fn weak_map() {
+        harness(include_str!("weak/weak_map.lox"));
+    }
+
+    #[test]
+    fn weak_ref() {
+        harness(include_str!("weak/weak_ref.lox"));
+    }
+
+    #[test]
+    fn weak_ref_number() {
+        harness(include_str!("weak/weak_ref_number.lox"));
+    }
+}
+
+mod import {
+    use super::harness;
+    use std::path::{Path, PathBuf};
+    use lox_vm::{ArchiveLoader, DirectoryLoader, ImportSite, LoadError, MemoryLoader, ModuleId, ModuleLoader, ModuleSource, VmError};
+
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tests/import").join(path)
+    }
+
+    #[test]
+    fn circular() {
+        harness(include_str!("import/circular.lox"));
+    }
+
+    #[test]
+    fn circular_message() {
+        let loader = MemoryLoader::new()
+            .with("main", "import \"a\";")
+            .with("a", "import \"b\";")
+            .with("b", "import \"a\";");
+
+        let err = match try_run(loader, &ModuleId::new("main")) {
+            Err(VmError::CircularImport(err)) => err,
+            result => panic!("expected a circular import, got {result:?}"),
+        };
+        assert_eq!(err.cycle, [ModuleId::new("b"), ModuleId::new("a")]);
+        assert_eq!(err.to_string(), "circular import, \"a\" imported from b: a -> b -> a");
+    }
+
+    #[test]
+    fn missing() {
+        harness(include_str!("import/missing.lox"));
+    }
+
+    /// The modules of a [`MemoryLoader`], and a module "locked" that cannot be read.
+    struct Locked(MemoryLoader);
+
+    impl ModuleLoader for Locked {
+        fn resolve(&self, from: &ModuleId, spec: &str) -> Result<ModuleId, LoadError> {
+            match spec {
+                "locked" => Ok(ModuleId::new("locked")),
+                spec => self.0.resolve(from, spec),
+            }
+        }
+
+        fn load(&self, id: &ModuleId) -> Result<ModuleSource, LoadError> {
+            match id.as_str() {
+                "locked" => Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied).into()),
+                _ => self.0.load(id),
+            }
+        }
+    }
+
+    #[test]
+    fn load_error() {
+        let main = ModuleId::new("main");
+        let loader = MemoryLoader::new()
+            .with("main", "import \"missing\";");
+
+        let err = match try_run(Locked(loader), &main) {
+            Err(VmError::Load(err)) => err,
+            result => panic!("expected a load error, got {result:?}"),
+        };
+        assert_eq!(err.module, None);
+        assert_eq!(*err.error, LoadError::NotFound);
+        assert_eq!(err.to_string(), "could not import \"missing\" imported from main: module not found");
+
+        let loader = MemoryLoader::new()
+            .with("main", "import \"locked\";");
+
+        let err = match try_run(Locked(loader), &main) {
+            Err(VmError::Load(err)) => err,
+            result => panic!("expected a load error, got {result:?}"),
+        };
+        assert_eq!(err.module, Some(ModuleId::new("locked")));
+        assert_eq!(err.site, Some(ImportSite {
+            importer: main,
+            path: "locked".to_string(),
+        }));
+        assert!(matches!(&*err.error, LoadError::Io(err) if err.kind() == std::io::ErrorKind::PermissionDenied));
+    }
+
+    #[test]
+    fn broken_import() {
+        harness(include_str!("import/broken_import.lox"));
+    }
+
+    #[test]
+    fn compile_error() {
+        let loader = MemoryLoader::new()
+            .with("main", "import \"lib/broken\";")
+            .with("lib/broken", include_str!("import/broken.lox"));
+
+        let err = match try_run(loader, &ModuleId::new("main")) {
+            Err(VmError::Import(err)) => err,
+            result => panic!("expected an import error, got {result:?}"),
+        };
+        assert_eq!(err.module.as_str(), "lib/broken");
+        assert_eq!(err.site, Some(ImportSite {
+            importer: ModuleId::new("main"),
+            path: "lib/broken".to_string(),
+        }));
+
+        let lines: Vec<_> = err.lines().map(|(line, diagnostic)| (line, diagnostic.message.as_str())).collect();
+        assert_eq!(lines, [(4, "Expected ';' got 'var'")]);
+    }
+
+    #[test]
+    fn native() {
+        harness(include_str!("import/native.lox"));
+    }
+
+    #[test]
+    fn native_shadows_exports() {
+        harness(include_str!("import/native_shadows_exports.lox"));
+    }
+
+    #[test]
+    fn namespace() {
+        harness(include_str!("import/namespace.lox"));
+    }
+
+    #[test]
+    fn namespace_hidden() {
+        harness(include_str!("import/namespace_hidden.lox"));
+    }
+
+    #[test]
+    fn not_exported() {
+        harness(include_str!("import/not_exported.lox"));
+    }
+
+    #[test]
+    fn export_local() {
+        harness(include_str!("import/export_local.lox"));
+    }
+
+    #[test]
+    fn directory_loader() {
+        let main = ModuleId::new(fixture("nested/main.lox").canonicalize().unwrap().to_string_lossy());
+        let helper = ModuleId::new(fixture("nested/helper.lox").canonicalize().unwrap().to_string_lossy());
+        let library = ModuleId::new(fixture("lib/library.lox").canonicalize().unwrap().to_string_lossy());
+
+        let loader = DirectoryLoader::new(Vec::<PathBuf>::new());
+        assert_eq!(loader.resolve(&main, "helper").unwrap(), helper);
+        assert_eq!(loader.resolve(&main, "../nested/helper").unwrap(), helper);
+        assert!(matches!(loader.resolve(&main, "library"), Err(LoadError::NotFound)));
+
+        let loader = DirectoryLoader::new([fixture("missing"), fixture("lib")]);
+        assert_eq!(loader.resolve(&main, "library").unwrap(), library);
+    }
+
+    #[test]
+    fn memory_loader() {
+        let loader = MemoryLoader::new()
+            .with("game/main", "")
+            .with("game/util", "")
+            .with("util", "")
+            .with("lib/list", "");
+
+        let main = ModuleId::new("game/main");
+        assert_eq!(loader.resolve(&main, "util").unwrap().as_str(), "game/util");
+        assert_eq!(loader.resolve(&main, "../util").unwrap().as_str(), "util");
+        assert_eq!(loader.resolve(&main, "./util").unwrap().as_str(), "game/util");
+        assert_eq!(loader.resolve(&main, "lib/list").unwrap().as_str(), "lib/list");
+        assert!(matches!(loader.resolve(&main, "missing"), Err(LoadError::NotFound)));
+    }
+
+    #[test]
+    fn archive_loader() {
+        let data = ArchiveLoader::bundle([
+            ("main", "import \"lib/library\" for library; print library();"),
+            ("lib/library", include_str!("import/lib/library.lox")),
+        ]);
+        let loader = ArchiveLoader::from_bytes(data.clone()).unwrap();
+        assert_eq!(loader.resolve(&ModuleId::new("main"), "lib/library").unwrap().as_str(), "lib/library");
+        assert!(ArchiveLoader::from_bytes(data[..data.len() - 1].to_vec()).is_err());
+        assert!(ArchiveLoader::from_bytes(b"not an archive".to_vec()).is_err());
+
+        let output = run(loader, &ModuleId::new("main"));
+        assert_eq!(output, ["library"]);
+    }
+
+    #[test]
+    fn files() {
+        let main = fixture("nested/main.lox").canonicalize().unwrap();
+        let source = std::fs::read_to_string(&main).unwrap();
+
+        let output = run(DirectoryLoader::new([fixture("lib")]), &ModuleId::new(main.to_string_lossy()));
+        let expects = super::parse_expects(&source, regex::Regex::new(r"// expect: ?(.*)").unwrap(), 1);
+        assert_eq!(expects, output);
+    }
+
+    #[test]
+    fn failed_import_runs_again() {
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|value| super::DATA.with(|data| data.lock().unwrap().push(value.into())));
+        vm.set_loader(MemoryLoader::new().with("import/failing", include_str!("import/failing.lox")));
+        lox_std::set_stdlib(&mut vm);
+
+        // The module whose top level failed is not kept half initialized, so the second import runs it again.
+        for _ in 0..2 {
+            let module = lox_compiler::compile("import \"import/failing\" for before; print before;").unwrap();
+            assert_eq!(vm.interpret(module), Err(VmError::GlobalNotDefined));
+        }
+
+        let output = super::DATA.with(|data| std::mem::take(&mut *data.lock().unwrap()));
+        assert_eq!(output, ["loading", "loading"]);
+        assert_eq!(vm.modules(), [ModuleId::new("_root")]);
+    }
+
+    /// Run the module `main` from `loader`, and return what it printed.
+    fn run(loader: impl ModuleLoader + 'static, main: &ModuleId) -> Vec<String> {
+        try_run(loader, main).unwrap()
+    }
+
+    fn try_run(loader: impl ModuleLoader + 'static, main: &ModuleId) -> Result<Vec<String>, VmError> {
+        let module = match loader.load(main).unwrap() {
+            ModuleSource::Source(source) => lox_compiler::compile(&source).unwrap(),
+            ModuleSource::Module(module) => module,
+        };
+
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|value| super::DATA.with(|data| data.lock().unwrap().push(value.into())));
+        vm.set_loader(loader);
+        lox_std::set_stdlib(&mut vm);
+        let result = vm.interpret_as(main.as_str(), module);
+
+        let output = super::DATA.with(|data| std::mem::take(&mut *data.lock().unwrap()));
+        result.map(|_| output)
+    }
+}
+
+mod builtins {
+    use super::harness;
+
+    #[test]
+    fn str() {
+        harness(include_str!("builtins/str.lox"));
+    }
+
+    #[test]
+    fn num() {
+        harness(include_str!("builtins/num.lox"));
+    }
+
+    #[test]
+    fn num_unsupported() {
+        harness(include_str!("builtins/num_unsupported.lox"));
+    }
+
+    #[test]
+    fn len() {
+        harness(include_str!("builtins/len.lox"));
+    }
+
+    #[test]
+    fn len_unsupported() {
+        harness(include_str!("builtins/len_unsupported.lox"));
+    }
+
+    #[test]
+    fn type_name() {
+        harness(include_str!("builtins/type.lox"));
+    }
+
+    #[test]
+    fn is() {
+        harness(include_str!("builtins/is.lox"));
+    }
+
+    #[test]
+    fn is_not_a_class() {
+        harness(include_str!("builtins/is_not_a_class.lox"));
+    }
+}
+
+mod reflect {
+    use super::harness;
+
+    #[test]
+    fn fields() {
+        harness(include_str!("reflect/fields.lox"));
+    }
+
+    #[test]
+    fn missing_field() {
+        harness(include_str!("reflect/missing_field.lox"));
+    }
+
+    #[test]
+    fn fields_of_non_instance() {
+        harness(include_str!("reflect/fields_of_non_instance.lox"));
+    }
+
+    #[test]
+    fn classes() {
+        harness(include_str!("reflect/classes.lox"));
+    }
+
+    #[test]
+    fn class_property() {
+        harness(include_str!("reflect/class_property.lox"));
+    }
+
+    #[test]
+    fn static_methods() {
+        harness(include_str!("reflect/static_methods.lox"));
+    }
+}
+
+mod math {
+    use super::harness;
+
+    #[test]
+    fn functions() {
+        harness(include_str!("math/functions.lox"));
+    }
+
+    #[test]
+    fn min_max() {
+        harness(include_str!("math/min_max.lox"));
+    }
+
+    #[test]
+    fn constants() {
+        harness(include_str!("math/constants.lox"));
+    }
+
+    #[test]
+    fn not_a_number() {
+        harness(include_str!("math/not_a_number.lox"));
+    }
+
+    #[test]
+    fn min_no_arguments() {
+        harness(include_str!("math/min_no_arguments.lox"));
+    }
+}
+
+mod io {
+    use super::harness;
+
+    #[test]
+    fn eprint() {
+        harness(include_str!("io/eprint.lox"));
+    }
+}
+
+mod fs {
+    use std::path::PathBuf;
+
+    /// Run `source` with the global `dir` set to a new, empty directory.
+    fn harness_in_dir(name: &str, source: &str) {
+        let dir: PathBuf = std::env::temp_dir().join(format!("lox-fs-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        super::harness(&format!("var dir = {:?};\n{source}", dir.to_str().unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files() {
+        harness_in_dir("files", include_str!("fs/files.lox"));
+    }
+
+    #[test]
+    fn missing_file() {
+        harness_in_dir("missing_file", include_str!("fs/missing_file.lox"));
+    }
+
+    #[test]
+    fn closed_file() {
+        harness_in_dir("closed_file", include_str!("fs/closed_file.lox"));
+    }
+
+    #[test]
+    fn error_kind() {
+        let path = std::env::temp_dir().join(format!("lox-fs-{}-error_kind/missing.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let module = lox_compiler::compile(&format!("import \"fs\" as fs; fs.readFile({path:?});")).unwrap();
+
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        lox_std::set_stdlib(&mut vm);
+        match vm.interpret(module) {
+            Err(lox_vm::VmError::Io(kind, message)) => {
+                assert_eq!(kind, std::io::ErrorKind::NotFound);
+                assert!(message.starts_with(&format!("{path}: ")), "{message}");
+            },
+            result => panic!("expected an io error, got {result:?}"),
+        }
+    }
+}
+
+mod os {
+    use super::harness;
+
+    #[test]
+    fn env() {
+        harness(include_str!("os/env.lox"));
+    }
+
+    #[test]
+    fn run() {
+        harness(include_str!("os/run.lox"));
+    }
+
+    #[test]
+    fn exit() {
+        harness(include_str!("os/exit.lox"));
+    }
+
+    #[test]
+    fn args() {
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|value| super::DATA.with(|data| data.lock().unwrap().push(value.into())));
+        lox_std::set_stdlib(&mut vm);
+        lox_std::set_args(&mut vm, vec!["first".to_string(), "--second".to_string()]);
+
+        let module = lox_compiler::compile("import \"os\" for args; print args;").unwrap();
+        vm.interpret(module).unwrap();
+
+        let output = super::DATA.with(|data| std::mem::take(&mut *data.lock().unwrap()));
+        assert_eq!(output, ["[first, --second]"]);
+    }
+}
+
+mod json {
+    use super::harness;
+
+    #[test]
+    fn stringify() {
+        harness(include_str!("json/stringify.lox"));
+    }
+
+    #[test]
+    fn parse() {
+        harness(include_str!("json/parse.lox"));
+    }
+
+    #[test]
+    fn parse_error() {
+        harness(include_str!("json/parse_error.lox"));
+    }
+
+    #[test]
+    fn cycle() {
+        harness(include_str!("json/cycle.lox"));
+    }
+
+    #[test]
+    fn unsupported() {
+        harness(include_str!("json/unsupported.lox"));
+    }
+
+    fn run(code: &str) -> Result<Vec<String>, lox_vm::VmError> {
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|value| super::DATA.with(|data| data.lock().unwrap().push(value.into())));
+        lox_std::set_stdlib(&mut vm);
+
+        let module = lox_compiler::compile(code).unwrap();
+        let result = vm.interpret(module);
+
+        let output = super::DATA.with(|data| std::mem::take(&mut *data.lock().unwrap()));
+        result.map(|_| output)
+    }
+
+    #[test]
+    fn objects() {
+        // Lox strings can't contain quotes, so the JSON is read from a file.
+        let path = std::env::temp_dir().join(format!("lox-json-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "b": { "c": [1, 2] }, "a": "text" }"#).unwrap();
+
+        let output = run(&format!(r#"import "json" as json; import "fs" as fs;
+            var value = json.parse(fs.readFile({:?}));
+            print value.a;
+            print value.b.c;
+            print json.stringify(value);"#, path.to_str().unwrap()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output.unwrap(), ["text", "[1, 2]", r#"{"a":"text","b":{"c":[1,2]}}"#]);
+    }
+
+    #[test]
+    fn parse_error_offset() {
+        let result = run(r#"import "json" as json; json.parse("[1,
+            2,, 3]");"#);
+        assert_eq!(result, Err(lox_vm::VmError::Parse("expected value at byte 18".to_string())));
+    }
+}
+
+mod time {
+    use super::harness;
+
+    #[test]
+    fn dates() {
+        harness(include_str!("time/dates.lox"));
+    }
+
+    #[test]
+    fn durations() {
+        harness(include_str!("time/durations.lox"));
+    }
+
+    #[test]
+    fn parse() {
+        harness(include_str!("time/parse.lox"));
+    }
+
+    #[test]
+    fn parse_error() {
+        harness(include_str!("time/parse_error.lox"));
+    }
+
+    #[test]
+    fn invalid_date() {
+        harness(include_str!("time/invalid_date.lox"));
+    }
+
+    #[test]
+    fn timestamp_out_of_range() {
+        harness(include_str!("time/timestamp_out_of_range.lox"));
+    }
+
+    #[test]
+    fn add_out_of_range() {
+        harness(include_str!("time/add_out_of_range.lox"));
+    }
+
+    #[test]
+    fn clocks() {
+        harness(include_str!("time/clocks.lox"));
+    }
+
+    #[test]
+    fn parse_error_offset() {
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        lox_std::set_stdlib(&mut vm);
+
+        let module = lox_compiler::compile(r#"import "time" as time; time.parse("2023-11-1x", "%Y-%m-%d");"#).unwrap();
+        assert_eq!(vm.interpret(module), Err(lox_vm::VmError::Parse("expected 2 digits at byte 8".to_string())));
+    }
+}
+
+mod random {
+    use super::harness;
+
+    #[test]
+    fn seeded() {
+        harness(include_str!("random/seeded.lox"));
+    }
+
+    #[test]
+    fn shared() {
+        harness(include_str!("random/shared.lox"));
+    }
+
+    #[test]
+    fn empty_choice() {
+        harness(include_str!("random/empty_choice.lox"));
+    }
+
+    #[test]
+    fn invalid_range() {
+        harness(include_str!("random/invalid_range.lox"));
+    }
+}
+
+mod reload {
+    use std::sync::{Arc, Mutex};
+    use lox_vm::{LoadError, ModuleId, ModuleLoader, ModuleSource, VmError};
+
+    /// A single module named "main", whose source can be changed while it runs.
+    #[derive(Clone, Default)]
+    struct Editable(Arc<Mutex<String>>);
+
+    impl ModuleLoader for Editable {
+        fn resolve(&self, _from: &ModuleId, spec: &str) -> Result<ModuleId, LoadError> {
+            Ok(ModuleId::new(spec))
+        }
+
+        fn load(&self, _id: &ModuleId) -> Result<ModuleSource, LoadError> {
+            Ok(ModuleSource::Source(self.0.lock().unwrap().clone()))
+        }
+    }
+
+    fn output() -> Vec<String> {
+        super::DATA.with(|data| std::mem::take(&mut *data.lock().unwrap()))
+    }
+
+    #[test]
+    fn reload_module() {
+        let source = Editable::default();
+        let main = ModuleId::new("main");
+
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|value| super::DATA.with(|data| data.lock().unwrap().push(value.into())));
+        vm.set_loader(source.clone());
+        lox_std::set_stdlib(&mut vm);
+
+        let module = lox_compiler::compile(include_str!("reload/before.lox")).unwrap();
+        vm.interpret_as(main.as_str(), module).unwrap();
+        assert_eq!(output(), ["hello"]);
+
+        *source.0.lock().unwrap() = include_str!("reload/after.lox").to_string();
+        vm.reload_module(&main).unwrap();
+        assert_eq!(output(), ["1", "hi"]);
+
+        // The instance made by the first version uses the new method, and the old closure sees the new global.
+        let check = lox_compiler::compile("import \"main\" as main; print main.greeter.greet(); print main.old();").unwrap();
+        vm.interpret_as("check", check).unwrap();
+        assert_eq!(output(), ["hi", "2"]);
+
+        // A version that does not compile leaves the running one alone.
+        *source.0.lock().unwrap() = include_str!("reload/broken.lox").to_string();
+        match vm.reload_module(&main) {
+            Err(VmError::Import(err)) => assert_eq!(err.site, None),
+            result => panic!("expected an import error, got {result:?}"),
+        }
+
+        *source.0.lock().unwrap() = "print count; print greeter.greet();".to_string();
+        vm.reload_module(&main).unwrap();
+        assert_eq!(output(), ["2", "hi"]);
+
+        assert!(matches!(vm.reload_module(&ModuleId::new("missing")), Err(VmError::UnknownImport)));
+    }
+
+    #[test]
+    fn failing_top_level() {
+        let source = Editable::default();
+        let main = ModuleId::new("main");
+
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_stdout(|value| super::DATA.with(|data| data.lock().unwrap().push(value.into())));
+        vm.set_loader(source.clone());
+        lox_std::set_stdlib(&mut vm);
+
+        let module = lox_compiler::compile(include_str!("reload/before.lox")).unwrap();
+        vm.interpret_as(main.as_str(), module).unwrap();
+        assert_eq!(output(), ["hello"]);
+
+        // The new top level redeclares the class before it fails.
+        *source.0.lock().unwrap() = include_str!("reload/failing.lox").to_string();
+        assert!(matches!(vm.reload_module(&main), Err(VmError::GlobalNotDefined)));
+
+        // The old version and its globals stay, so its instances still belong to the class.
+        let check = lox_compiler::compile(
+            "import \"main\" as main; print main.count; print main.greeter.greet(); print is(main.greeter, main.Greeter); print classOf(main.greeter) == main.Greeter;",
+        ).unwrap();
+        vm.interpret_as("check", check).unwrap();
+        assert_eq!(output(), ["1", "hello", "true", "true"]);
+    }
+}
+
 mod assignment {
     use super::harness;
     #[test]
@@ -208,6 +1032,26 @@ mod class {
         harness(include_str!("class/empty.lox"));
     }
 
+    #[test]
+    fn duplicate_field() {
+        harness(include_str!("class/duplicate_field.lox"));
+    }
+
+    #[test]
+    fn field_initializer_scope() {
+        harness(include_str!("class/field_initializer_scope.lox"));
+    }
+
+    #[test]
+    fn fields() {
+        harness(include_str!("class/fields.lox"));
+    }
+
+    #[test]
+    fn fields_before_init() {
+        harness(include_str!("class/fields_before_init.lox"));
+    }
+
     #[test]
     #[ignore = "not yet implemented"]
     fn inherit_self() {
@@ -241,6 +1085,26 @@ mod class {
     fn reference_self() {
         harness(include_str!("class/reference_self.lox"));
     }
+
+    #[test]
+    fn static_field_undeclared() {
+        harness(include_str!("class/static_field_undeclared.lox"));
+    }
+
+    #[test]
+    fn static_init() {
+        harness(include_str!("class/static_init.lox"));
+    }
+
+    #[test]
+    fn static_members() {
+        harness(include_str!("class/static_members.lox"));
+    }
+
+    #[test]
+    fn static_not_on_instance() {
+        harness(include_str!("class/static_not_on_instance.lox"));
+    }
 }
 
 mod closure {
@@ -1236,6 +2100,10 @@ mod variable {
         harness(include_str!("variable/unreached_undefined.lox"));
     }
     #[test]
+    fn use_contextual_keyword_as_var() {
+        harness(include_str!("variable/use_contextual_keyword_as_var.lox"));
+    }
+    #[test]
     fn use_false_as_var() {
         harness(include_str!("variable/use_false_as_var.lox"));
     }
@@ -1257,6 +2125,52 @@ mod variable {
     }
 }
 
+mod thread {
+    use super::harness;
+
+    #[test]
+    fn copy() {
+        harness(include_str!("thread/copy.lox"));
+    }
+
+    #[test]
+    fn cycle() {
+        harness(include_str!("thread/cycle.lox"));
+    }
+
+    #[test]
+    fn echo() {
+        harness(include_str!("thread/echo.lox"));
+    }
+
+    #[test]
+    fn join_error() {
+        harness(include_str!("thread/join_error.lox"));
+    }
+
+    #[test]
+    fn unknown_module() {
+        harness(include_str!("thread/unknown_module.lox"));
+    }
+
+    #[test]
+    fn worker_heap_config() {
+        let module = lox_compiler::compile("spawn(\"thread/echo_worker\").join();").unwrap();
+
+        let mut vm = lox_vm::VirtualMachine::new().unwrap();
+        vm.set_loader(super::loader());
+        vm.set_worker_heap_config(lox_vm::HeapConfig { reserve_bytes: 0, ..lox_vm::HeapConfig::default() });
+        lox_std::set_stdlib(&mut vm);
+        match vm.interpret(module) {
+            Err(lox_vm::VmError::Io(kind, message)) => {
+                assert_eq!(kind, std::io::ErrorKind::InvalidInput);
+                assert!(message.starts_with("could not reserve the heap of thread/echo_worker: "), "{message}");
+            },
+            result => panic!("expected an io error, got {result:?}"),
+        }
+    }
+}
+
 mod r#while {
     use super::harness;
 